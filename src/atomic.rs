@@ -0,0 +1,162 @@
+use std::fs;
+use std::io::{ self, Write };
+use std::path;
+use std::process;
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+/// A file that is written into a temporary file and moved into place only when it is committed.
+///
+/// Returned by [`CacheDir::create_atomic`]. The temporary file is created in the same directory
+/// as the destination, so it is on the same file system and it can be renamed into place.<br/>
+/// Until [`commit`] is called, the other threads and processes keep observing the previous file
+/// (or no file). If the `AtomicFile` is dropped without being committed(ex: because of an error
+/// or a panic), the temporary file is removed.
+///
+/// # Examples
+/// ```
+/// # use cachedir::CacheDirConfig;
+/// use std::io::Write;
+///
+/// let cache_dir = CacheDirConfig::new("example")
+///                                .get_cache_dir().unwrap();
+///
+/// let mut file = cache_dir.create_atomic("index/data.bin").unwrap();
+/// file.write_all(b"first part").unwrap();
+/// file.write_all(b"second part").unwrap();
+/// file.commit().unwrap();
+/// ```
+///
+/// [`CacheDir::create_atomic`]: struct.CacheDir.html#method.create_atomic
+/// [`commit`]: struct.AtomicFile.html#method.commit
+#[derive(Debug)]
+pub struct AtomicFile {
+    // `None` after the file was committed or aborted
+    file:     Option<fs::File>,
+    tmp_path: path::PathBuf,
+    path:     path::PathBuf
+}
+
+impl AtomicFile {
+    // Creates the temporary file for `path`
+    pub(crate) fn new(path: path::PathBuf) -> io::Result<AtomicFile> {
+        let tmp_path = tmp_path(&path);
+        let file = fs::OpenOptions::new()
+                       .write(true)
+                       .create_new(true)
+                       .open(&tmp_path)?;
+
+        Ok(AtomicFile { file: Some(file), tmp_path, path })
+    }
+
+    /// The path of the destination file.
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    // Changes the destination file, for when it is known only after writing the contents
+    #[cfg(feature = "blob-store")]
+    pub(crate) fn set_path(&mut self, path: path::PathBuf) {
+        self.path = path;
+    }
+
+    /// Flushes the contents to the disk(`fsync`) and renames the temporary file into place,
+    /// replacing the previous file.
+    ///
+    /// On Windows, the file is replaced with `MoveFileExW(MOVEFILE_REPLACE_EXISTING)`.
+    ///
+    /// # Errors
+    /// If the contents could not be flushed or if the file could not be renamed, in which case
+    /// the temporary file is removed and the previous file is left untouched.
+    pub fn commit(mut self) -> io::Result<()> {
+        let result = match self.file.take() {
+            // The file is closed before renaming it, which is required on Windows
+            Some(file) => file.sync_all().and_then(|_| {
+                drop(file);
+                fs::rename(&self.tmp_path, &self.path)
+            }),
+            None       => Ok(())
+        };
+
+        if result.is_err() {
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+        result
+    }
+
+    /// Removes the temporary file, leaving the previous file untouched.
+    ///
+    /// This is the same as dropping the `AtomicFile`, but the error is reported.
+    pub fn abort(mut self) -> io::Result<()> {
+        match self.file.take() {
+            Some(file) => {
+                drop(file);
+                fs::remove_file(&self.tmp_path)
+            },
+            None       => Ok(())
+        }
+    }
+
+    fn file(&mut self) -> io::Result<&mut fs::File> {
+        self.file.as_mut().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::Other, "The atomic file was already committed or aborted")
+        })
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file()?.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            drop(file);
+            let _ = fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+// Returns a unique path for a temporary file next to `path`, so it is on the same file system
+// and it can be renamed into place. The name is unique between the processes and the threads
+fn tmp_path(path: &path::Path) -> path::PathBuf {
+    static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.{}.tmp", file_name, process::id(),
+                                TMP_COUNTER.fetch_add(1, Ordering::Relaxed)))
+}
+
+// Returns `true` if `path` is named like the temporary files of `tmp_path`(`.<name>.<pid>.<n>.tmp`)
+pub fn is_tmp_file(path: &path::Path) -> bool {
+    let stem = path.file_name()
+                   .and_then(|name| name.to_str())
+                   .and_then(|name| name.strip_prefix('.'))
+                   .and_then(|name| name.strip_suffix(".tmp"));
+    let stem = match stem {
+        Some(stem) => stem,
+        None       => return false
+    };
+
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let mut parts = stem.rsplitn(3, '.');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(counter), Some(pid), Some(name)) => {
+            is_number(counter) && is_number(pid) && !name.is_empty()
+        },
+        _                                      => false
+    }
+}
+
+// Writes `contents` into a temporary file and renames it into place, so the other processes
+// observe either the previous file or the new one, never a partially written file
+pub fn write(path: &path::Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = AtomicFile::new(path.to_path_buf())?;
+    file.write_all(contents)?;
+    file.commit()
+}
@@ -0,0 +1,173 @@
+use std::fmt;
+use std::fs;
+use std::io::{ self, Read, Write };
+use std::path;
+use std::str;
+
+use sha2::{ Digest as _, Sha256 };
+
+use atomic::AtomicFile;
+
+// The blobs are stored in `<cache_dir>/blobs/<algorithm>/ab/abcdef...`
+const BLOBS_DIR: &str = "blobs";
+const ALGORITHM: &str = "sha256";
+
+/// The SHA-256 hash of a blob's contents, which is also its address in the blob store.
+///
+/// Returned by [`CacheDir::store_blob`] and used by [`CacheDir::open_blob`].
+/// It is displayed as `sha256:<hex>`.
+///
+/// ```
+/// # use cachedir::Digest;
+/// let hex = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+/// let digest = Digest::from_hex(hex).unwrap();
+///
+/// assert_eq!(hex, digest.to_hex());
+/// assert_eq!(format!("sha256:{}", hex), digest.to_string());
+/// ```
+///
+/// [`CacheDir::store_blob`]: struct.CacheDir.html#method.store_blob
+/// [`CacheDir::open_blob`]: struct.CacheDir.html#method.open_blob
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Digest {
+    hash: [u8; 32]
+}
+
+impl Digest {
+    /// Creates a digest from the raw bytes of a SHA-256 hash.
+    pub fn from_bytes(hash: [u8; 32]) -> Digest {
+        Digest { hash }
+    }
+
+    /// Parses the hex encoded SHA-256 hash(64 characters, in lower or upper case).
+    ///
+    /// Returns `None` if `hex` is not a valid hash.
+    pub fn from_hex(hex: &str) -> Option<Digest> {
+        // `from_str_radix` alone would also accept a sign(ex: `+f`)
+        let bytes = hex.as_bytes();
+        if bytes.len() != 64 || !bytes.iter().all(u8::is_ascii_hexdigit) {
+            return None;
+        }
+
+        let mut hash = [0u8; 32];
+        for (byte, pair) in hash.iter_mut().zip(bytes.chunks(2)) {
+            // `pair` is ASCII, so it is valid UTF-8
+            *byte = u8::from_str_radix(str::from_utf8(pair).ok()?, 16).ok()?;
+        }
+        Some(Digest { hash })
+    }
+
+    /// The name of the hash algorithm(always `sha256`), as used in the blob paths.
+    pub fn algorithm(&self) -> &'static str {
+        ALGORITHM
+    }
+
+    /// The raw bytes of the hash.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.hash
+    }
+
+    /// The hash encoded as 64 lowercase hex characters.
+    pub fn to_hex(&self) -> String {
+        self.hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", ALGORITHM, self.to_hex())
+    }
+}
+
+/// A reader of a blob that verifies the contents against the blob's [`Digest`].
+///
+/// Returned by [`CacheDir::open_blob`]. The contents are hashed while they are read and,
+/// when the end of the blob is reached, an `InvalidData` error is returned instead of the end
+/// of the file if the contents don't match the digest(ex: the file was truncated or modified).
+/// <br/>
+/// The contents are verified only if the blob is read until the end.
+///
+/// [`Digest`]: struct.Digest.html
+/// [`CacheDir::open_blob`]: struct.CacheDir.html#method.open_blob
+#[derive(Debug)]
+pub struct BlobReader {
+    file:     fs::File,
+    hasher:   Sha256,
+    digest:   Digest,
+    verified: bool
+}
+
+impl BlobReader {
+    /// The digest that the contents are verified against.
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+}
+
+impl Read for BlobReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.file.read(buf)?;
+        if read > 0 {
+            self.hasher.update(&buf[..read]);
+        } else if !buf.is_empty() && !self.verified {
+            let found = Digest { hash: self.hasher.clone().finalize().into() };
+            if found != self.digest {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("The blob is corrupted: expected {}, found {}",
+                                                  self.digest, found)));
+            }
+            self.verified = true;
+        }
+        Ok(read)
+    }
+}
+
+// Returns `<cache_dir>/blobs/sha256/ab/abcdef...`, the first 2 characters of the hash
+// are used as a subdirectory, so a single directory does not end up with too many files
+pub fn blob_path(cache_dir: &path::Path, digest: &Digest) -> path::PathBuf {
+    let hash = digest.to_hex();
+    cache_dir.join(BLOBS_DIR).join(ALGORITHM).join(&hash[..2]).join(hash)
+}
+
+// Streams `reader` into a temporary file while hashing it and moves the file to the path
+// of its digest. If a blob with the same contents is already stored, the temporary file
+// is removed instead
+pub fn store(cache_dir: &path::Path, mut reader: impl Read) -> io::Result<Digest> {
+    let algorithm_dir = cache_dir.join(BLOBS_DIR).join(ALGORITHM);
+    fs::create_dir_all(&algorithm_dir)?;
+
+    // The final path is known only after hashing, so the temporary file is created next to
+    // the shards, on the same file system
+    let mut file = AtomicFile::new(algorithm_dir.join("blob"))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0)                                                    => break,
+            Ok(read)                                                 => read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err)                                                 => return Err(err)
+        };
+        hasher.update(&buf[..read]);
+        file.write_all(&buf[..read])?;
+    }
+
+    let digest = Digest { hash: hasher.finalize().into() };
+    let path = blob_path(cache_dir, &digest);
+    if path.is_file() {
+        file.abort()?;
+        return Ok(digest);
+    }
+
+    if let Some(shard) = path.parent() {
+        fs::create_dir_all(shard)?;
+    }
+    file.set_path(path);
+    file.commit()?;
+    Ok(digest)
+}
+
+pub fn open(cache_dir: &path::Path, digest: &Digest) -> io::Result<BlobReader> {
+    let file = fs::File::open(blob_path(cache_dir, digest))?;
+    Ok(BlobReader { file, hasher: Sha256::new(), digest: *digest, verified: false })
+}
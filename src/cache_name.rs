@@ -0,0 +1,130 @@
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+use std::path;
+
+use target_os::TargetOs;
+
+/// The reason why a cache name was rejected by `CacheDirConfig`.
+///
+/// Cache names are joined to the parent cache directories, so they are validated to make sure
+/// that the final cache directory can not escape its parent directory.
+///
+/// Read [`CacheDirConfig::new`] documentation if you want to find more about the validation.
+///
+/// [`CacheDirConfig::new`]: struct.CacheDirConfig.html#method.new
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum CacheNameError {
+    /// The cache name is empty(or it contains only `.` components).
+    Empty,
+    /// The cache name contains a `..` component.
+    ParentDir,
+    /// The cache name is an absolute path(it has a root or a Windows prefix like `C:`).
+    Absolute,
+    /// The cache name contains a name that is reserved on Windows(ex: `CON`, `NUL`, `COM1`).
+    Reserved(String),
+    /// The cache name has multiple components(ex: `org/app`), but nested cache names
+    /// were not allowed with [`nested_cache_name(true)`].
+    ///
+    /// [`nested_cache_name(true)`]: struct.CacheDirConfig.html#method.nested_cache_name
+    Nested
+}
+
+impl fmt::Display for CacheNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CacheNameError::Empty              => f.write_str("The cache name is empty"),
+            CacheNameError::ParentDir          => {
+                f.write_str("The cache name must not contain `..` components")
+            },
+            CacheNameError::Absolute           => {
+                f.write_str("The cache name must be a relative path")
+            },
+            CacheNameError::Reserved(ref name) => {
+                write!(f, "The cache name contains a name reserved on Windows: {}", name)
+            },
+            CacheNameError::Nested             => {
+                f.write_str("The cache name has multiple components, but nested cache names \
+                             are not allowed")
+            }
+        }
+    }
+}
+
+impl error::Error for CacheNameError {}
+
+// Device names that can not be used as file names on Windows, with or without an extension
+const RESERVED_NAMES: [&str; 26] = ["CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$",
+                                    "COM0", "COM1", "COM2", "COM3", "COM4",
+                                    "COM5", "COM6", "COM7", "COM8", "COM9",
+                                    "LPT0", "LPT1", "LPT2", "LPT3", "LPT4",
+                                    "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"];
+
+fn is_reserved(name: &str) -> bool {
+    // Windows ignores the extension and the trailing spaces: "nul .txt" is also reserved
+    let stem = name.split('.').next().unwrap_or("").trim_end_matches(' ');
+    RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+// Makes sure that `name` stays inside of the directory it gets joined to.
+// The reserved Windows names are rejected on all the systems, to keep the cache names portable
+pub fn validate(name: &path::Path, allow_nested: bool) -> Result<(), CacheNameError> {
+    let mut components: Vec<Cow<str>> = Vec::new();
+    for component in name.components() {
+        match component {
+            path::Component::Prefix(_) |
+            path::Component::RootDir   => return Err(CacheNameError::Absolute),
+            component                  => components.push(component.as_os_str().to_string_lossy())
+        }
+    }
+    validate_components(components, allow_nested)
+}
+
+// Same as `validate`, but with the path rules of `target_os`, which can be another OS than
+// the current one(ex: `a\..\..` escapes its parent directory only on Windows)
+pub fn validate_for(target_os:    TargetOs,
+                    name:         &path::Path,
+                    allow_nested: bool) -> Result<(), CacheNameError> {
+    if target_os.separator() == path::MAIN_SEPARATOR {
+        return validate(name, allow_nested);
+    }
+
+    let name = name.to_string_lossy();
+    let is_separator = |c: char| c == '/' || (target_os == TargetOs::Windows && c == '\\');
+
+    // A root(`/name`, `\name` or `\\server\share`) or a drive prefix(`C:name` is relative
+    // to the current directory of the drive, so it is rejected too)
+    let mut chars = name.chars();
+    let has_drive_prefix = target_os == TargetOs::Windows
+                           && chars.next().map_or(false, |c| c.is_ascii_alphabetic())
+                           && chars.next() == Some(':');
+    if name.starts_with(is_separator) || has_drive_prefix {
+        return Err(CacheNameError::Absolute);
+    }
+
+    validate_components(name.split(is_separator).filter(|c| !c.is_empty()).map(Cow::from),
+                        allow_nested)
+}
+
+fn validate_components<'a, I>(components: I, allow_nested: bool) -> Result<(), CacheNameError>
+    where I: IntoIterator<Item = Cow<'a, str>>
+{
+    let mut normal_components = 0;
+    for component in components {
+        match &*component {
+            ".."                                => return Err(CacheNameError::ParentDir),
+            "."                                 => {},
+            component if is_reserved(component) => {
+                return Err(CacheNameError::Reserved(component.to_owned()));
+            },
+            _                                   => normal_components += 1
+        }
+    }
+
+    match normal_components {
+        0                     => Err(CacheNameError::Empty),
+        1                     => Ok(()),
+        _ if allow_nested     => Ok(()),
+        _                     => Err(CacheNameError::Nested)
+    }
+}
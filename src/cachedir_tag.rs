@@ -0,0 +1,40 @@
+use std::path::Path;
+use std::io::{ self, Read };
+use std::fs;
+
+use atomic;
+
+// See: https://bford.info/cachedir/
+pub const FILE_NAME: &str = "CACHEDIR.TAG";
+const SIGNATURE:     &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+const CONTENTS:      &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55\n\
+                               # This file is a cache directory tag created by cachedir.\n\
+                               # For information about cache directory tags, see:\n\
+                               #\thttps://bford.info/cachedir/\n";
+
+// A tag is valid if the file starts with the signature, the rest of the file is ignored
+pub fn is_tagged(cache_dir: &Path) -> bool {
+    let mut header = [0u8; 43];
+    fs::File::open(cache_dir.join(FILE_NAME))
+        .and_then(|mut file| file.read_exact(&mut header))
+        .map(|_| header[..] == *SIGNATURE)
+        .unwrap_or(false)
+}
+
+// Writes the tag atomically(see `atomic::write`), so other processes never observe
+// a partially written tag. A valid tag that already exists is left untouched, including
+// one that was written by another thread or process while this one was writing it
+pub fn tag(cache_dir: &Path) -> io::Result<()> {
+    if is_tagged(cache_dir) {
+        return Ok(());
+    }
+
+    match atomic::write(&cache_dir.join(FILE_NAME), CONTENTS) {
+        Ok(())                         => Ok(()),
+        Err(_) if is_tagged(cache_dir) => Ok(()),
+        Err(err)                       => {
+            Err(io::Error::new(err.kind(),
+                               format!("Failed to write the {} file: {}", FILE_NAME, err)))
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use std::io;
+use std::path;
+
+use kind::CacheKind;
+
+/// A location where `CacheDirConfig` would attempt to create the cache directory.
+///
+/// Returned by [`CacheDirConfig::candidates`], which computes the candidates without
+/// creating anything. The locations that would be skipped are reported too, with
+/// the [`reason`] why they would be skipped.
+///
+/// [`CacheDirConfig::candidates`]: struct.CacheDirConfig.html#method.candidates
+/// [`reason`]: struct.Candidate.html#method.reason
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Candidate {
+    kind:   CacheKind,
+    path:   path::PathBuf,
+    status: CandidateStatus,
+    reason: Option<String>
+}
+
+impl Candidate {
+    pub(crate) fn new(kind: CacheKind, path: path::PathBuf, status: CandidateStatus) -> Candidate {
+        Candidate { kind, path, status, reason: None }
+    }
+
+    // A location that would be skipped because of `err`
+    pub(crate) fn rejected(kind:   CacheKind,
+                           path:   path::PathBuf,
+                           status: CandidateStatus,
+                           err:    &io::Error) -> Candidate {
+        Candidate { kind, path, status, reason: Some(err.to_string()) }
+    }
+
+    /// The kind of cache this candidate belongs to.
+    pub fn kind(&self) -> CacheKind {
+        self.kind
+    }
+
+    /// The path of the cache directory that would be created(or reused).
+    ///
+    /// The path is empty if it could not be determined(`CandidateStatus::Unavailable`).
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    /// The state of the candidate at the moment when it was computed.
+    pub fn status(&self) -> CandidateStatus {
+        self.status
+    }
+
+    /// Why the candidate would be skipped without attempting to create the cache directory
+    /// (ex: `$XDG_CACHE_HOME` is a relative path), `None` for the other candidates.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
+
+/// The state of a [`Candidate`], checked without touching the filesystem.
+///
+/// The state can change before the cache directory is created, so it is only a prediction
+/// of what `get_cache_dir` will do.
+///
+/// [`Candidate`]: struct.Candidate.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CandidateStatus {
+    /// The cache directory already exists and is writable.
+    Exists,
+    /// The cache directory already exists, but it is not writable.
+    ExistsReadOnly,
+    /// The cache directory does not exist, but it can be created.
+    Creatable,
+    /// The cache directory does not exist and the closest existing parent is not writable.
+    NotWritable,
+    /// The path of the cache directory, or of one of its parents, is not a directory.
+    NotADirectory,
+    /// The cache directory(or one of its parents) is inside of a directory shared with
+    /// other users(ex: `/var/tmp`) and it is a symbolic link or it is owned by another user.
+    Insecure,
+    /// The parent cache directory does not exist and it would not be created.
+    MissingParent,
+    /// The location is not valid(ex: `$XDG_CACHE_HOME` or `$XDG_RUNTIME_DIR` is a relative path).
+    Invalid,
+    /// The location could not be determined(ex: the home directory is not known, or the kind
+    /// of cache is not supported on the OS).
+    Unavailable,
+    /// The location is not stored in RAM, but the memory cache is required to be
+    /// (ex: `$XDG_RUNTIME_DIR` on a disk).
+    NotRamBacked,
+    /// The memory cache is required to be stored in RAM, but the type of the file system
+    /// of the location can not be determined on the OS(only Linux and Android are supported).
+    Unverifiable,
+    /// The file system of the location has less free space than required.
+    InsufficientSpace
+}
+
+impl CandidateStatus {
+    /// Returns `true` if `get_cache_dir` is expected to succeed with this candidate.
+    pub fn is_usable(&self) -> bool {
+        matches!(*self, CandidateStatus::Exists | CandidateStatus::Creatable)
+    }
+
+    /// Returns `true` if `get_cache_dir` would skip the location without attempting to create
+    /// the cache directory(`Invalid`, `Unavailable`, `NotRamBacked`, `Unverifiable`
+    /// and `InsufficientSpace`).
+    pub fn is_rejected(&self) -> bool {
+        matches!(*self, CandidateStatus::Invalid
+                      | CandidateStatus::Unavailable
+                      | CandidateStatus::NotRamBacked
+                      | CandidateStatus::Unverifiable
+                      | CandidateStatus::InsufficientSpace)
+    }
+}
+
+/// A location where the cache directory would be created on a [`TargetOs`], which can be
+/// another OS than the current one.
+///
+/// Returned by [`CacheDirConfig::locations`], which only computes the paths. The locations
+/// that would be skipped on the target OS are reported too, with the [`reason`] why.
+///
+/// [`TargetOs`]: enum.TargetOs.html
+/// [`CacheDirConfig::locations`]: struct.CacheDirConfig.html#method.locations
+/// [`reason`]: struct.CacheLocation.html#method.reason
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheLocation {
+    kind:   CacheKind,
+    path:   path::PathBuf,
+    status: Option<CandidateStatus>,
+    reason: Option<String>
+}
+
+impl CacheLocation {
+    pub(crate) fn new(kind: CacheKind, path: path::PathBuf) -> CacheLocation {
+        CacheLocation { kind, path, status: None, reason: None }
+    }
+
+    // A location that would be skipped on the target OS because of `err`
+    pub(crate) fn rejected(kind:   CacheKind,
+                           path:   path::PathBuf,
+                           status: CandidateStatus,
+                           err:    &io::Error) -> CacheLocation {
+        CacheLocation { kind, path, status: Some(status), reason: Some(err.to_string()) }
+    }
+
+    /// The kind of cache this location belongs to.
+    pub fn kind(&self) -> CacheKind {
+        self.kind
+    }
+
+    /// The path of the cache directory, with the path separators of the target OS.
+    ///
+    /// The path is empty if it could not be determined(`CandidateStatus::Unavailable`).
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    /// `None` if the location would be attempted on the target OS, otherwise why it would be
+    /// skipped: `CandidateStatus::Invalid` or `CandidateStatus::Unavailable`(the file systems
+    /// are not checked, because they belong to the target system).
+    pub fn status(&self) -> Option<CandidateStatus> {
+        self.status
+    }
+
+    /// Why the location would be skipped on the target OS, `None` if it would be attempted.
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+}
@@ -0,0 +1,255 @@
+use std::cmp;
+use std::fs;
+use std::io;
+use std::path;
+use std::time::{ Duration, SystemTime };
+
+use atomic;
+use cachedir_tag;
+use locking;
+
+// The temporary files of the atomic writes are collected only if they were not modified for
+// this long, otherwise they are probably still being written(ex: by another process)
+const TMP_GRACE_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The result of removing files from a cache directory.
+///
+/// Returned by [`CacheDir::enforce_limit`] and [`CacheDir::prune`]. The files that could not be
+/// read or removed are recorded in [`errors`], they don't stop the cleanup.
+///
+/// [`CacheDir::enforce_limit`]: struct.CacheDir.html#method.enforce_limit
+/// [`CacheDir::prune`]: struct.CacheDir.html#method.prune
+/// [`errors`]: struct.CleanupReport.html#method.errors
+#[derive(Debug, Default)]
+pub struct CleanupReport {
+    removed:        Vec<path::PathBuf>,
+    dirs_removed:   usize,
+    bytes_freed:    u64,
+    remaining_size: u64,
+    errors:         Vec<(path::PathBuf, io::Error)>
+}
+
+impl CleanupReport {
+    /// The files that were removed, in the order in which they were removed.
+    pub fn removed(&self) -> &[path::PathBuf] {
+        &self.removed
+    }
+
+    /// The number of files that were removed.
+    pub fn files_removed(&self) -> usize {
+        self.removed.len()
+    }
+
+    /// The number of empty subdirectories that were removed.
+    pub fn dirs_removed(&self) -> usize {
+        self.dirs_removed
+    }
+
+    /// The total size of the removed files, in bytes.
+    pub fn bytes_freed(&self) -> u64 {
+        self.bytes_freed
+    }
+
+    /// The total size of the files that were left in the cache directory, in bytes.
+    pub fn remaining_size(&self) -> u64 {
+        self.remaining_size
+    }
+
+    /// The paths that could not be read or removed, together with the reason.
+    pub fn errors(&self) -> &[(path::PathBuf, io::Error)] {
+        &self.errors
+    }
+}
+
+// A regular file from the cache directory
+struct CacheFile {
+    path:      path::PathBuf,
+    size:      u64,
+    // The latest of the access and the modification times, because the access time
+    // is not updated on the file systems mounted with `noatime`
+    last_used: SystemTime
+}
+
+// The contents of the cache directory
+struct CacheContents {
+    files:   Vec<CacheFile>,
+    // The subdirectories, every directory comes after its parent
+    subdirs: Vec<path::PathBuf>
+}
+
+// Collects all the regular files and the subdirectories under `cache_dir`, without following
+// symbolic links. The `CACHEDIR.TAG` file, the lock of the cache directory and the lock files
+// of the existing entries are not collected, because they are not cache entries. The lock files
+// of the missing entries are collected, so they are cleaned up too. The temporary files of
+// the atomic writes are collected only after `TMP_GRACE_PERIOD`, when they were left behind
+fn cache_contents(cache_dir: &path::Path,
+                  report:    &mut CleanupReport) -> io::Result<CacheContents> {
+    let mut files = Vec::new();
+    let mut subdirs = Vec::new();
+    let mut dirs = vec![cache_dir.to_path_buf()];
+    let now = SystemTime::now();
+    let tag_path = cache_dir.join(cachedir_tag::FILE_NAME);
+    let dir_lock_path = cache_dir.join(locking::DIR_LOCK_NAME);
+    let is_entry_lock = |path: &path::Path| {
+        locking::locked_entry_path(path).map_or(false, |entry_path| entry_path.is_file())
+    };
+
+    // Only the cache directory itself must be readable, the errors from
+    // its subdirectories are recorded in the report
+    fs::read_dir(cache_dir)?;
+
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err)    => {
+                report.errors.push((dir, err));
+                continue;
+            }
+        };
+
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err)  => {
+                    report.errors.push((dir.clone(), err));
+                    continue;
+                }
+            };
+
+            let path = entry.path();
+            // `DirEntry::metadata` does not follow symbolic links
+            let metadata = match entry.metadata() {
+                Ok(metadata) => metadata,
+                Err(err)     => {
+                    report.errors.push((path, err));
+                    continue;
+                }
+            };
+
+            if metadata.is_dir() {
+                subdirs.push(path.clone());
+                dirs.push(path);
+            } else if metadata.is_file() && path != tag_path && path != dir_lock_path
+                      && !is_entry_lock(&path) {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                // A modification time in the future is not old enough either
+                let is_live_tmp = atomic::is_tmp_file(&path)
+                                  && now.duration_since(modified)
+                                        .map_or(true, |age| age < TMP_GRACE_PERIOD);
+                if is_live_tmp {
+                    continue;
+                }
+
+                let accessed = metadata.accessed().unwrap_or(modified);
+                files.push(CacheFile { path,
+                                       size:      metadata.len(),
+                                       last_used: cmp::max(accessed, modified) });
+            }
+        }
+    }
+
+    Ok(CacheContents { files, subdirs })
+}
+
+// Removes a file of the cache directory. An entry that has a lock file is removed together with
+// it, while holding the exclusive lock, and so is a lock file whose entry is missing.
+// Returns `Ok(false)` without removing anything if the lock is held by another process
+fn remove_file(path: &path::Path) -> io::Result<bool> {
+    let lock_path = match locking::locked_entry_path(path) {
+        Some(_) => path.to_path_buf(),
+        None    => locking::entry_lock_path(path)
+    };
+
+    match locking::try_lock_existing(lock_path) {
+        Ok(Some(lock))                                        => {
+            if lock.path() != path {
+                fs::remove_file(path)?;
+            }
+            lock.remove().map(|_| true)
+        },
+        Ok(None)                                              => Ok(false),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+            fs::remove_file(path).map(|_| true)
+        },
+        Err(err)                                              => Err(err)
+    }
+}
+
+// Computes the total size of the regular files under `cache_dir`
+pub fn size(cache_dir: &path::Path) -> io::Result<u64> {
+    let mut report = CleanupReport::default();
+    let contents = cache_contents(cache_dir, &mut report)?;
+    Ok(contents.files.iter().map(|file| file.size).sum())
+}
+
+// Removes the least recently used files until the total size is not larger than `limit`.
+// The entries that are locked by other processes are kept
+pub fn enforce_limit(cache_dir: &path::Path, limit: u64) -> io::Result<CleanupReport> {
+    let mut report = CleanupReport::default();
+    let mut files = cache_contents(cache_dir, &mut report)?.files;
+    let mut total_size: u64 = files.iter().map(|file| file.size).sum();
+
+    files.sort_by_key(|file| file.last_used);
+    for file in files {
+        if total_size <= limit {
+            break;
+        }
+
+        match remove_file(&file.path) {
+            Ok(true)  => {
+                total_size -= file.size;
+                report.bytes_freed += file.size;
+                report.removed.push(file.path);
+            },
+            Ok(false) => {},
+            Err(err)  => report.errors.push((file.path, err))
+        }
+    }
+
+    report.remaining_size = total_size;
+    Ok(report)
+}
+
+// Removes the files that were not used for `older_than` and the empty subdirectories.
+// The entries that are locked by other processes are kept
+pub fn prune(cache_dir: &path::Path, older_than: Duration) -> io::Result<CleanupReport> {
+    let mut report = CleanupReport::default();
+    let contents = cache_contents(cache_dir, &mut report)?;
+
+    // Nothing can be older than the start of the time
+    let threshold = SystemTime::now().checked_sub(older_than);
+    for file in contents.files {
+        if threshold.map_or(true, |threshold| file.last_used >= threshold) {
+            report.remaining_size += file.size;
+            continue;
+        }
+
+        match remove_file(&file.path) {
+            Ok(true)  => {
+                report.bytes_freed += file.size;
+                report.removed.push(file.path);
+            },
+            Ok(false) => report.remaining_size += file.size,
+            Err(err)  => {
+                report.remaining_size += file.size;
+                report.errors.push((file.path, err));
+            }
+        }
+    }
+
+    // The children are removed before their parents, so the directories
+    // that contained only empty directories are removed too
+    for dir in contents.subdirs.iter().rev() {
+        let is_empty = fs::read_dir(dir).map(|mut entries| entries.next().is_none());
+        match is_empty {
+            Ok(true)  => match fs::remove_dir(dir) {
+                Ok(_)    => report.dirs_removed += 1,
+                Err(err) => report.errors.push((dir.clone(), err))
+            },
+            Ok(false) => {},
+            Err(err)  => report.errors.push((dir.clone(), err))
+        }
+    }
+
+    Ok(report)
+}
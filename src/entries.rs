@@ -0,0 +1,129 @@
+use std::fs;
+use std::io::{ self, Read, Write };
+use std::path;
+
+use atomic;
+use locking;
+
+// FNV-1a(128 bits) parameters, from http://www.isthe.com/chongo/tech/comp/fnv/
+const FNV_OFFSET_BASIS: u128 = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+const FNV_PRIME:        u128 = 0x0000_0000_0100_0000_0000_0000_0000_013b;
+
+fn fnv1a_128(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u128::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+// Maps a key to `<cache_dir>/ab/cdef...`, where `abcdef...` is the hex encoded hash of the key.
+// The first 2 characters are used as a subdirectory, so a single directory does not end up
+// with too many files
+pub fn entry_path(cache_dir: &path::Path, key: &[u8]) -> path::PathBuf {
+    let hash = format!("{:032x}", fnv1a_128(key));
+    let (shard, name) = hash.split_at(2);
+    cache_dir.join(shard).join(name)
+}
+
+// The entry files start with the key(its length as a little endian `u64`, then its bytes),
+// so a hash collision is read as a missing entry instead of as the contents of another key
+fn write_key(file: &mut atomic::AtomicFile, key: &[u8]) -> io::Result<()> {
+    file.write_all(&(key.len() as u64).to_le_bytes())?;
+    file.write_all(key)
+}
+
+// Creates the shard directory and the temporary file of the entry
+fn create_entry(path: &path::Path) -> io::Result<atomic::AtomicFile> {
+    if let Some(shard) = path.parent() {
+        fs::create_dir_all(shard)?;
+    }
+    atomic::AtomicFile::new(path.to_path_buf())
+}
+
+// Opens the entry file of `key`, positioned after the key. Returns `None` if there is no entry
+// file or if it stores another key(or a truncated key)
+fn open_entry(cache_dir: &path::Path, key: &[u8]) -> io::Result<Option<fs::File>> {
+    let mut file = match fs::File::open(entry_path(cache_dir, key)) {
+        Ok(file)                                              => file,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err)                                              => return Err(err)
+    };
+
+    let mut len = [0u8; 8];
+    if !read_exact_or_eof(&mut file, &mut len)? || u64::from_le_bytes(len) != key.len() as u64 {
+        return Ok(None);
+    }
+    let mut stored_key = vec![0u8; key.len()];
+    if !read_exact_or_eof(&mut file, &mut stored_key)? || stored_key != key {
+        return Ok(None);
+    }
+    Ok(Some(file))
+}
+
+// Like `read_exact`, but returns `false` instead of an error if the end of the file is reached
+fn read_exact_or_eof(file: &mut fs::File, buf: &mut [u8]) -> io::Result<bool> {
+    match file.read_exact(buf) {
+        Ok(_)                                                      => Ok(true),
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err)                                                   => Err(err)
+    }
+}
+
+pub fn get(cache_dir: &path::Path, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    match open_entry(cache_dir, key)? {
+        Some(mut file) => {
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+            Ok(Some(contents))
+        },
+        None           => Ok(None)
+    }
+}
+
+pub fn put(cache_dir: &path::Path, key: &[u8], contents: &[u8]) -> io::Result<()> {
+    let path = entry_path(cache_dir, key);
+    let mut file = match create_entry(&path) {
+        // `prune` could have removed the empty shard directory after it was created
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => create_entry(&path)?,
+        result                                                => result?
+    };
+    write_key(&mut file, key)?;
+    file.write_all(contents)?;
+    file.commit()
+}
+
+// The lock file of the entry is removed too, while holding its lock, unless the lock is held
+// by somebody else(waiting for it could deadlock, if the caller holds it)
+// The entry file of a colliding key is left untouched
+pub fn remove(cache_dir: &path::Path, key: &[u8]) -> io::Result<bool> {
+    if open_entry(cache_dir, key)?.is_none() {
+        return Ok(false);
+    }
+
+    let path = entry_path(cache_dir, key);
+    let lock = match locking::try_lock_existing(locking::entry_lock_path(&path)) {
+        Ok(lock)                                              => lock,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err)                                              => return Err(err)
+    };
+
+    let removed = match fs::remove_file(&path) {
+        Ok(_)                                                 => true,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => false,
+        Err(err)                                              => return Err(err)
+    };
+    if let Some(lock) = lock {
+        lock.remove()?;
+    }
+    Ok(removed)
+}
+
+pub fn contains(cache_dir: &path::Path, key: &[u8]) -> io::Result<bool> {
+    match fs::symlink_metadata(entry_path(cache_dir, key)) {
+        Ok(ref metadata) if metadata.is_file()                => {
+            open_entry(cache_dir, key).map(|file| file.is_some())
+        },
+        Ok(_)                                                 => Ok(false),
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err)                                              => Err(err)
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{ OsStr, OsString };
+use std::fmt;
+use std::io;
+use std::path;
+
+/// The source of the information about the current process that the cache locations
+/// are computed from.
+///
+/// `CacheDirConfig` uses the [`ProcessEnvironment`] by default. Another environment can be
+/// passed with [`CacheDirConfig::environment`](ex: a [`CustomEnvironment`]), so the cache
+/// locations can be computed for an arbitrary environment without modifying the environment
+/// of the process.
+///
+/// Only the locations are computed from the environment, the checks made when creating
+/// the cache directory(ex: the owner of a directory inside of `/var/tmp`) always use
+/// the real process.
+///
+/// [`ProcessEnvironment`]: struct.ProcessEnvironment.html
+/// [`CacheDirConfig::environment`]: struct.CacheDirConfig.html#method.environment
+/// [`CustomEnvironment`]: struct.CustomEnvironment.html
+pub trait Environment: fmt::Debug + Send + Sync {
+    /// The home directory of the current user according to the OS, regardless of
+    /// the environment variables(ex: `$HOME`): the passwd entry of the user on Unix
+    /// and the `Profile` known folder on Windows.
+    ///
+    /// `CacheDirConfig` prefers `$HOME` on Unix and uses `%USERPROFILE%` only as
+    /// a fallback on Windows.
+    fn home_dir(&self) -> Option<path::PathBuf>;
+
+    /// The value of the `name` environment variable, if it is set.
+    fn var_os(&self, name: &str) -> Option<OsString>;
+
+    /// The directory for temporary files(ex: `/tmp`), or an empty path if it is not known.
+    fn temp_dir(&self) -> path::PathBuf;
+
+    /// The current working directory.
+    fn current_dir(&self) -> io::Result<path::PathBuf>;
+
+    /// The effective user id of the current user on Unix, `None` on the other systems.
+    fn uid(&self) -> Option<u32>;
+
+    /// The security identifier(SID) of the current user on Windows(ex: `S-1-5-21-...-1001`),
+    /// obtained from the token of the process, `None` on the other systems.
+    ///
+    /// Like the uid on Unix, it identifies the user in the shared cache directories and it
+    /// can't be spoofed by changing the environment variables(unlike `%USERNAME%`).
+    fn user_sid(&self) -> Option<String>;
+
+    /// The path of a known folder of the current user on Windows(obtained with
+    /// `SHGetKnownFolderPath`), `None` on the other systems.
+    ///
+    /// `CacheDirConfig` prefers the known folders over their environment variables
+    /// (ex: `%LOCALAPPDATA%`), which can be missing or spoofed(ex: in services).
+    fn known_folder(&self, folder: KnownFolder) -> Option<path::PathBuf>;
+}
+
+/// The Windows known folders that the cache locations are computed from.
+///
+/// Used by [`Environment::known_folder`].
+///
+/// [`Environment::known_folder`]: trait.Environment.html#tymethod.known_folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KnownFolder {
+    /// `FOLDERID_LocalAppData`(ex: `C:\Users\<user>\AppData\Local`), the fallback is
+    /// `%LOCALAPPDATA%`.
+    LocalAppData,
+    /// `FOLDERID_ProgramData`(ex: `C:\ProgramData`), the fallback is `%ProgramData%`.
+    ProgramData
+}
+
+/// The environment of the current process, read from `std::env` and from the OS
+/// (ex: the passwd entry of the user on Unix, the known folders on Windows).
+///
+/// This is the environment used by `CacheDirConfig` by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct ProcessEnvironment;
+
+impl Environment for ProcessEnvironment {
+    // `std::env::home_dir` is not used because it reads `$HOME` on Windows
+    // and it doesn't ignore an empty `$HOME`
+    #[cfg(unix)]
+    fn home_dir(&self) -> Option<path::PathBuf> {
+        use std::ffi::CStr;
+        use std::mem;
+        use std::os::raw::c_char;
+        use std::os::unix::ffi::OsStrExt;
+        use std::ptr;
+        use libc;
+
+        let mut buf: Vec<c_char> = vec![0; 1024];
+        let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+        let mut result: *mut libc::passwd = ptr::null_mut();
+        loop {
+            let code = unsafe {
+                libc::getpwuid_r(libc::geteuid(), &mut passwd, buf.as_mut_ptr(), buf.len(),
+                                 &mut result)
+            };
+            match code {
+                // The buffer is too small for the strings of the passwd entry
+                libc::ERANGE if buf.len() < 1024 * 1024 => buf.resize(buf.len() * 2, 0),
+                libc::EINTR                             => continue,
+                _                                       => break
+            }
+        }
+
+        if result.is_null() || passwd.pw_dir.is_null() {
+            return None;
+        }
+        let pw_dir = unsafe { CStr::from_ptr(passwd.pw_dir) };
+        Some(path::PathBuf::from(OsStr::from_bytes(pw_dir.to_bytes())))
+    }
+
+    #[cfg(windows)]
+    fn home_dir(&self) -> Option<path::PathBuf> {
+        use winapi::um::knownfolders::FOLDERID_Profile;
+
+        known_folder_path(&FOLDERID_Profile)
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn home_dir(&self) -> Option<path::PathBuf> {
+        None
+    }
+
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        env::var_os(name)
+    }
+
+    fn temp_dir(&self) -> path::PathBuf {
+        env::temp_dir()
+    }
+
+    fn current_dir(&self) -> io::Result<path::PathBuf> {
+        env::current_dir()
+    }
+
+    #[cfg(unix)]
+    fn uid(&self) -> Option<u32> {
+        use libc;
+
+        Some(unsafe { libc::geteuid() })
+    }
+
+    #[cfg(not(unix))]
+    fn uid(&self) -> Option<u32> {
+        None
+    }
+
+    #[cfg(windows)]
+    fn user_sid(&self) -> Option<String> {
+        use std::ptr;
+        use std::slice;
+        use winapi::shared::sddl::ConvertSidToStringSidW;
+        use winapi::um::handleapi::CloseHandle;
+        use winapi::um::processthreadsapi::{ GetCurrentProcess, OpenProcessToken };
+        use winapi::um::securitybaseapi::GetTokenInformation;
+        use winapi::um::winbase::LocalFree;
+        use winapi::um::winnt::{ HANDLE, LPWSTR, TOKEN_QUERY, TOKEN_USER, TokenUser };
+
+        let mut token: HANDLE = ptr::null_mut();
+        if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+            return None;
+        }
+
+        // The first call returns the size of `TOKEN_USER` together with the SID it points to.
+        // The buffer is made of `u64`s, so it is aligned for `TOKEN_USER`
+        let mut len = 0;
+        unsafe { GetTokenInformation(token, TokenUser, ptr::null_mut(), 0, &mut len) };
+        let mut buf: Vec<u64> = vec![0; len as usize / 8 + 1];
+        let result = unsafe {
+            GetTokenInformation(token, TokenUser, buf.as_mut_ptr() as _, (buf.len() * 8) as _,
+                                &mut len)
+        };
+        unsafe { CloseHandle(token) };
+        if result == 0 {
+            return None;
+        }
+
+        let token_user = unsafe { &*(buf.as_ptr() as *const TOKEN_USER) };
+        let mut sid: LPWSTR = ptr::null_mut();
+        if unsafe { ConvertSidToStringSidW(token_user.User.Sid, &mut sid) } == 0 {
+            return None;
+        }
+        let len = (0..).take_while(|&i| unsafe { *sid.offset(i) } != 0).count();
+        let user_sid = String::from_utf16(unsafe { slice::from_raw_parts(sid, len) }).ok();
+
+        // The string is allocated by `ConvertSidToStringSidW`
+        unsafe { LocalFree(sid as _) };
+        user_sid
+    }
+
+    #[cfg(not(windows))]
+    fn user_sid(&self) -> Option<String> {
+        None
+    }
+
+    #[cfg(windows)]
+    fn known_folder(&self, folder: KnownFolder) -> Option<path::PathBuf> {
+        use winapi::um::knownfolders::{ FOLDERID_LocalAppData, FOLDERID_ProgramData };
+
+        known_folder_path(match folder {
+            KnownFolder::LocalAppData => &FOLDERID_LocalAppData,
+            KnownFolder::ProgramData  => &FOLDERID_ProgramData
+        })
+    }
+
+    #[cfg(not(windows))]
+    fn known_folder(&self, _: KnownFolder) -> Option<path::PathBuf> {
+        None
+    }
+}
+
+// Returns the path of a known folder(ex: `FOLDERID_Profile`) of the current user
+#[cfg(windows)]
+fn known_folder_path(folder_id: &::winapi::shared::guiddef::GUID) -> Option<path::PathBuf> {
+    use std::os::windows::ffi::OsStringExt;
+    use std::ptr;
+    use std::slice;
+    use winapi::shared::winerror::S_OK;
+    use winapi::um::combaseapi::CoTaskMemFree;
+    use winapi::um::shlobj::SHGetKnownFolderPath;
+    use winapi::um::winnt::PWSTR;
+
+    let mut path: PWSTR = ptr::null_mut();
+    let result = unsafe { SHGetKnownFolderPath(folder_id, 0, ptr::null_mut(), &mut path) };
+    let folder = if result == S_OK && !path.is_null() {
+        let len = (0..).take_while(|&i| unsafe { *path.offset(i) } != 0).count();
+        let wide = unsafe { slice::from_raw_parts(path, len) };
+        Some(path::PathBuf::from(OsString::from_wide(wide)))
+    } else {
+        None
+    };
+
+    // The buffer has to be freed even when the call fails
+    unsafe { CoTaskMemFree(path as _) };
+    folder
+}
+
+/// An environment made of the values that were explicitly set, everything else is unknown.
+///
+/// The setters are named after the `std::env` functions(ex: `set_var`), so they don't clash
+/// with the methods of the [`Environment`] trait.
+///
+/// Useful for computing where the cache directory would be created for another user or
+/// in tests, without depending on the environment of the current process.
+///
+/// # Examples
+/// ```
+/// use cachedir::{ CacheDirConfig, CustomEnvironment };
+/// use std::path::Path;
+///
+/// let candidates = CacheDirConfig::new("example")
+///                                 .environment(CustomEnvironment::new()
+///                                                                .set_home_dir("/home/user")
+///                                                                .set_uid(1000))
+///                                 .candidates().unwrap();
+///
+/// if cfg!(target_os = "linux") {
+///     assert_eq!(Path::new("/home/user/.cache/example"), candidates[0].path());
+/// }
+/// ```
+///
+/// [`Environment`]: trait.Environment.html
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CustomEnvironment {
+    home_dir:      Option<path::PathBuf>,
+    vars:          HashMap<String, OsString>,
+    temp_dir:      Option<path::PathBuf>,
+    current_dir:   Option<path::PathBuf>,
+    uid:           Option<u32>,
+    user_sid:      Option<String>,
+    known_folders: HashMap<KnownFolder, path::PathBuf>
+}
+
+impl CustomEnvironment {
+    /// Creates an environment where nothing is known: there is no home directory,
+    /// no environment variable, no temporary directory, no current directory, no uid,
+    /// no user SID and no known folder.
+    pub fn new() -> CustomEnvironment {
+        CustomEnvironment::default()
+    }
+
+    /// Sets the home directory of the current user according to the OS(ex: its passwd entry).
+    ///
+    /// On Unix, `$HOME` still takes precedence over it if it is set with [`set_var`].
+    ///
+    /// [`set_var`]: struct.CustomEnvironment.html#method.set_var
+    pub fn set_home_dir<P>(&mut self, path: &P) -> &mut CustomEnvironment
+        where P: AsRef<OsStr> + ?Sized
+    {
+        self.home_dir = Some(path::PathBuf::from(path));
+        self
+    }
+
+    /// Sets the `name` environment variable to `value`.
+    pub fn set_var<V>(&mut self, name: &str, value: &V) -> &mut CustomEnvironment
+        where V: AsRef<OsStr> + ?Sized
+    {
+        self.vars.insert(name.to_owned(), value.as_ref().to_owned());
+        self
+    }
+
+    /// Sets the directory for temporary files.
+    pub fn set_temp_dir<P>(&mut self, path: &P) -> &mut CustomEnvironment
+        where P: AsRef<OsStr> + ?Sized
+    {
+        self.temp_dir = Some(path::PathBuf::from(path));
+        self
+    }
+
+    /// Sets the current working directory.
+    pub fn set_current_dir<P>(&mut self, path: &P) -> &mut CustomEnvironment
+        where P: AsRef<OsStr> + ?Sized
+    {
+        self.current_dir = Some(path::PathBuf::from(path));
+        self
+    }
+
+    /// Sets the effective user id of the current user.
+    pub fn set_uid(&mut self, uid: u32) -> &mut CustomEnvironment {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Sets the security identifier(SID) of the current user on Windows.
+    pub fn set_user_sid(&mut self, sid: &str) -> &mut CustomEnvironment {
+        self.user_sid = Some(sid.to_owned());
+        self
+    }
+
+    /// Sets the path of a Windows known folder.
+    pub fn set_known_folder<P>(&mut self, folder: KnownFolder, path: &P) -> &mut CustomEnvironment
+        where P: AsRef<OsStr> + ?Sized
+    {
+        self.known_folders.insert(folder, path::PathBuf::from(path));
+        self
+    }
+}
+
+impl Environment for CustomEnvironment {
+    fn home_dir(&self) -> Option<path::PathBuf> {
+        self.home_dir.clone()
+    }
+
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        self.vars.get(name).cloned()
+    }
+
+    fn temp_dir(&self) -> path::PathBuf {
+        self.temp_dir.clone().unwrap_or_default()
+    }
+
+    fn current_dir(&self) -> io::Result<path::PathBuf> {
+        self.current_dir.clone().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "The current directory is not set")
+        })
+    }
+
+    fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    fn user_sid(&self) -> Option<String> {
+        self.user_sid.clone()
+    }
+
+    fn known_folder(&self, folder: KnownFolder) -> Option<path::PathBuf> {
+        self.known_folders.get(&folder).cloned()
+    }
+}
@@ -0,0 +1,157 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path;
+
+use cache_name::CacheNameError;
+use kind::CacheKind;
+
+/// The error returned by [`get_cache_dir`] when none of the configured cache locations
+/// could be used.
+///
+/// It records every attempt that `CacheDirConfig` made, in the order in which they were made,
+/// so it is possible to find out why each fallback was rejected.
+///
+/// If the cache name was rejected, no attempts are made and [`invalid_cache_name`] returns
+/// the reason.
+///
+/// `CacheDirError` converts into an `std::io::Error`(with the `std::io::ErrorKind` of the last
+/// attempt), so it can be propagated with `?` from functions that return `std::io::Result`.
+///
+/// # Examples
+/// ```
+/// # use cachedir::CacheDirConfig;
+/// # use std::io;
+/// fn cache_path() -> io::Result<String> {
+///     let cache_dir = CacheDirConfig::new("example")
+///                                    .get_cache_dir()?;
+///     Ok(cache_dir.display().to_string())
+/// }
+///
+/// match CacheDirConfig::new("example").try_all_caches().get_cache_dir() {
+///     Ok(cache_dir) => println!("Using: {}", cache_dir.display()),
+///     Err(err)      => {
+///         for attempt in err.attempts() {
+///             eprintln!("{} was rejected: {}", attempt.kind(), attempt.error());
+///         }
+///     }
+/// }
+/// ```
+///
+/// [`get_cache_dir`]: struct.CacheDirConfig.html#method.get_cache_dir
+/// [`invalid_cache_name`]: struct.CacheDirError.html#method.invalid_cache_name
+#[derive(Debug)]
+pub struct CacheDirError {
+    attempts:     Vec<Attempt>,
+    invalid_name: Option<CacheNameError>
+}
+
+impl CacheDirError {
+    pub(crate) fn new(attempts: Vec<Attempt>) -> CacheDirError {
+        CacheDirError { attempts, invalid_name: None }
+    }
+
+    /// The reason why the cache name was rejected, if it was rejected.
+    pub fn invalid_cache_name(&self) -> Option<&CacheNameError> {
+        self.invalid_name.as_ref()
+    }
+
+    /// All the failed attempts, in the order in which they were made.
+    pub fn attempts(&self) -> &[Attempt] {
+        &self.attempts
+    }
+
+    /// The `std::io::ErrorKind` of the last failed attempt(`NotFound` if there were no attempts).
+    ///
+    /// If the cache name was rejected, `InvalidInput` is returned.
+    pub fn kind(&self) -> io::ErrorKind {
+        if self.invalid_name.is_some() {
+            return io::ErrorKind::InvalidInput;
+        }
+
+        self.attempts.last()
+                     .map(|attempt| attempt.error.kind())
+                     .unwrap_or(io::ErrorKind::NotFound)
+    }
+}
+
+impl fmt::Display for CacheDirError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref invalid_name) = self.invalid_name {
+            return write!(f, "Could not create the cache directory: {}", invalid_name);
+        }
+
+        f.write_str("Could not create the cache directory")?;
+        for attempt in &self.attempts {
+            write!(f, "\n{}", attempt)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for CacheDirError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        if let Some(ref invalid_name) = self.invalid_name {
+            return Some(invalid_name);
+        }
+
+        self.attempts.last().map(|attempt| &attempt.error as &(dyn error::Error + 'static))
+    }
+}
+
+impl From<CacheNameError> for CacheDirError {
+    fn from(err: CacheNameError) -> CacheDirError {
+        CacheDirError { attempts: Vec::new(), invalid_name: Some(err) }
+    }
+}
+
+impl From<CacheDirError> for io::Error {
+    fn from(err: CacheDirError) -> io::Error {
+        io::Error::new(err.kind(), err)
+    }
+}
+
+/// A failed attempt to create the cache directory in one of the cache locations.
+#[derive(Debug)]
+pub struct Attempt {
+    kind:  CacheKind,
+    path:  Option<path::PathBuf>,
+    error: io::Error
+}
+
+impl Attempt {
+    pub(crate) fn new(kind: CacheKind, path: Option<path::PathBuf>, error: io::Error) -> Attempt {
+        Attempt { kind, path, error }
+    }
+
+    /// The kind of cache that was attempted.
+    pub fn kind(&self) -> CacheKind {
+        self.kind
+    }
+
+    /// The path that was attempted, if the cache location could be determined.
+    pub fn path(&self) -> Option<&path::Path> {
+        self.path.as_deref()
+    }
+
+    /// The reason why the attempt failed.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for Attempt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.path {
+            Some(ref path) => write!(f, "[{}][{:?}]: {}: {}",
+                                     self.kind, self.error.kind(), path.display(), self.error),
+            None           => write!(f, "[{}][{:?}]: {}", self.kind, self.error.kind(), self.error)
+        }
+    }
+}
+
+impl error::Error for Attempt {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+use std::io;
+use std::path;
+
+/// Information about the file system on which a cache directory is stored.
+///
+/// Returned by [`CacheDir::fs_info`]. The values are read when the cache directory
+/// is selected, so the free space is only a snapshot.
+///
+/// [`CacheDir::fs_info`]: struct.CacheDir.html#method.fs_info
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FsInfo {
+    fs_type:    FsType,
+    free_space: u64
+}
+
+impl FsInfo {
+    /// The type of the file system.
+    pub fn fs_type(&self) -> FsType {
+        self.fs_type
+    }
+
+    /// The number of bytes that are available to the current user.
+    pub fn free_space(&self) -> u64 {
+        self.free_space
+    }
+
+    /// Returns `true` if the file system is stored in RAM(a `tmpfs` or a `ramfs`).
+    ///
+    /// This is a shortcut for `fs_info.fs_type().is_ram_backed()`.
+    pub fn is_ram_backed(&self) -> bool {
+        self.fs_type.is_ram_backed()
+    }
+}
+
+/// The type of a file system, as far as `CacheDirConfig` is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FsType {
+    /// `tmpfs` - stored in RAM(and swap), usually mounted on `/dev/shm` and `/run/user/<uid>`.
+    Tmpfs,
+    /// `ramfs` - stored in RAM, without a size limit.
+    Ramfs,
+    /// Any other file system, identified by its magic number(ex: `0xEF53` for `ext4`).
+    Other(u64),
+    /// The type of the file system can not be determined on this OS.
+    Unknown
+}
+
+impl FsType {
+    /// Returns `true` for `Tmpfs` and `Ramfs`.
+    pub fn is_ram_backed(&self) -> bool {
+        matches!(*self, FsType::Tmpfs | FsType::Ramfs)
+    }
+}
+
+impl fmt::Display for FsType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FsType::Tmpfs        => write!(f, "tmpfs"),
+            FsType::Ramfs        => write!(f, "ramfs"),
+            FsType::Other(magic) => write!(f, "file system {:#x}", magic),
+            FsType::Unknown      => write!(f, "unknown file system")
+        }
+    }
+}
+
+// Reads the information about the file system that contains `path`
+pub fn query(path: &path::Path) -> io::Result<FsInfo> {
+    Ok(FsInfo { fs_type: fs_type(path)?, free_space: free_space(path)? })
+}
+
+#[cfg(unix)]
+fn c_path(path: &path::Path) -> io::Result<::std::ffi::CString> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    CString::new(path.as_os_str().as_bytes())
+           .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[allow(clippy::unnecessary_cast)]
+fn fs_type(path: &path::Path) -> io::Result<FsType> {
+    use std::mem;
+    use libc;
+
+    // Missing from `libc`, from `linux/magic.h`
+    const RAMFS_MAGIC: u64 = 0x8584_58f6;
+
+    let path = c_path(path)?;
+    let mut stat: libc::statfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // The type of `f_type` depends on the architecture and on the C library
+    let magic = stat.f_type as u64;
+    Ok(match magic {
+        magic if magic == libc::TMPFS_MAGIC as u64 => FsType::Tmpfs,
+        RAMFS_MAGIC                               => FsType::Ramfs,
+        magic                                     => FsType::Other(magic)
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn fs_type(_: &path::Path) -> io::Result<FsType> {
+    Ok(FsType::Unknown)
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)]
+fn free_space(path: &path::Path) -> io::Result<u64> {
+    use std::mem;
+    use libc;
+
+    let path = c_path(path)?;
+    let mut stat: libc::statvfs = unsafe { mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // `f_bavail` counts the blocks available to unprivileged users, in `f_frsize` units
+    Ok((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+#[cfg(windows)]
+fn free_space(path: &path::Path) -> io::Result<u64> {
+    use std::mem;
+    use std::ptr;
+    use std::os::windows::ffi::OsStrExt;
+    use winapi::um::fileapi::GetDiskFreeSpaceExW;
+    use winapi::um::winnt::ULARGE_INTEGER;
+
+    let path: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available: ULARGE_INTEGER = unsafe { mem::zeroed() };
+    let result = unsafe {
+        GetDiskFreeSpaceExW(path.as_ptr(), &mut available, ptr::null_mut(), ptr::null_mut())
+    };
+    if result == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(unsafe { *available.QuadPart() })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn free_space(_: &path::Path) -> io::Result<u64> {
+    Err(io::Error::new(io::ErrorKind::Other, "The free space can not be determined on this OS"))
+}
@@ -0,0 +1,125 @@
+use std::path;
+
+use target_os::TargetOs;
+
+/// Identifies an application by its qualifier, organization and application name, from which
+/// the cache name that is idiomatic on each operating system is derived.
+///
+/// | OS                     | Cache name                      | Example          |
+/// |------------------------|---------------------------------|------------------|
+/// | macOS                  | `qualifier.Organization.App`    | `com.Org.App`    |
+/// | Windows                | `Organization\App\Cache`        | `Org\App\Cache`  |
+/// | Linux and the others   | `organization/app`              | `org/app`        |
+///
+/// On macOS the name is the reverse-DNS bundle identifier of the application, which is what
+/// Apple expects under `~/Library/Caches`(the user caches directory returned by
+/// `NSSearchPathForDirectoriesInDomains`), the spaces and the characters that are not allowed
+/// in bundle identifiers are replaced with `-`.<br/>
+/// On Windows the caches directories are shared with the other application data, so the name
+/// ends with a dedicated `Cache` directory.<br/>
+/// On the other systems the name is lowercase and the spaces are replaced with `-`.
+///
+/// Empty parts are skipped(ex: an application without a qualifier is `Org.App` on macOS)
+/// and path separators inside of the parts are replaced with `-`.
+///
+/// Use [`CacheDirConfig::from_app_identity`] to create a cache directory with the name for
+/// the current OS, or [`cache_name`] to compute the name for any OS.
+///
+/// # Examples
+/// ```
+/// use cachedir::{ AppIdentity, TargetOs };
+/// use std::path::Path;
+///
+/// let identity = AppIdentity::new("com", "Example Corp", "Build Tool");
+///
+/// assert_eq!(Path::new("com.Example-Corp.Build-Tool"), identity.cache_name(TargetOs::MacOs));
+/// assert_eq!(Path::new("example-corp/build-tool"), identity.cache_name(TargetOs::Linux));
+/// assert_eq!(Path::new(r"Example Corp\Build Tool\Cache"), identity.cache_name(TargetOs::Windows));
+/// ```
+///
+/// [`CacheDirConfig::from_app_identity`]: struct.CacheDirConfig.html#method.from_app_identity
+/// [`cache_name`]: struct.AppIdentity.html#method.cache_name
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AppIdentity {
+    qualifier:    String,
+    organization: String,
+    application:  String
+}
+
+impl AppIdentity {
+    /// `qualifier` is usually the top-level domain of the organization(ex: `com`, `org`),
+    /// `organization` and `application` are the names displayed to the users.
+    pub fn new(qualifier: &str, organization: &str, application: &str) -> AppIdentity {
+        AppIdentity {
+            qualifier:    qualifier.to_owned(),
+            organization: organization.to_owned(),
+            application:  application.to_owned()
+        }
+    }
+
+    /// The qualifier(ex: `com`).
+    pub fn qualifier(&self) -> &str {
+        &self.qualifier
+    }
+
+    /// The name of the organization.
+    pub fn organization(&self) -> &str {
+        &self.organization
+    }
+
+    /// The name of the application.
+    pub fn application(&self) -> &str {
+        &self.application
+    }
+
+    /// Computes the cache name that is idiomatic on `target_os`, without touching
+    /// the filesystem.
+    ///
+    /// The name is relative to the parent cache directory and it can have multiple components,
+    /// which are joined with the path separator of `target_os`(ex: `Org\App\Cache` on Windows,
+    /// even when it is computed on Linux).
+    pub fn cache_name(&self, target_os: TargetOs) -> path::PathBuf {
+        match target_os {
+            TargetOs::MacOs   => {
+                let parts: Vec<String> = [&self.qualifier, &self.organization, &self.application]
+                                             .iter()
+                                             .filter(|part| !part.is_empty())
+                                             .map(|part| bundle_id_part(part))
+                                             .collect();
+                path::PathBuf::from(parts.join("."))
+            },
+            TargetOs::Windows => {
+                let mut parts: Vec<String> = [&self.organization, &self.application]
+                                                 .iter()
+                                                 .filter(|part| !part.is_empty())
+                                                 .map(|part| replace_separators(part))
+                                                 .collect();
+                parts.push("Cache".to_owned());
+                path::PathBuf::from(parts.join(&target_os.separator().to_string()))
+            },
+            _                 => {
+                let parts: Vec<String> = [&self.organization, &self.application]
+                                             .iter()
+                                             .filter(|part| !part.is_empty())
+                                             .map(|part| {
+                                                 replace_separators(&part.to_lowercase())
+                                                     .replace(' ', "-")
+                                             })
+                                             .collect();
+                // `PathBuf` would use the separator of the current OS
+                path::PathBuf::from(parts.join(&target_os.separator().to_string()))
+            }
+        }
+    }
+}
+
+// Bundle identifiers can contain only alphanumeric characters, `-` and `.`
+fn bundle_id_part(part: &str) -> String {
+    part.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '-' })
+        .collect()
+}
+
+fn replace_separators(part: &str) -> String {
+    part.replace(['/', '\\'], "-")
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+/// The kinds of cache locations that `CacheDirConfig` can attempt to create
+/// a cache directory in.
+///
+/// Read the [`CacheDirConfig`] documentation if you want to find more about each of them.
+///
+/// [`CacheDirConfig`]: struct.CacheDirConfig.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CacheKind {
+    /// Application cache - see [`app_cache`](struct.CacheDirConfig.html#method.app_cache)
+    App,
+    /// User cache - see [`user_cache`](struct.CacheDirConfig.html#method.user_cache)
+    User,
+    /// System-wide cache - see [`sys_cache`](struct.CacheDirConfig.html#method.sys_cache)
+    System,
+    /// Tmp cache - see [`tmp_cache`](struct.CacheDirConfig.html#method.tmp_cache)
+    Tmp,
+    /// Memory cache - see [`mem_cache`](struct.CacheDirConfig.html#method.mem_cache)
+    Memory
+}
+
+impl CacheKind {
+    /// Describes how long the data stored in this kind of cache is expected to survive.
+    ///
+    /// # Examples
+    /// ```
+    /// use cachedir::{ CacheKind, Persistence };
+    ///
+    /// assert_eq!(Persistence::Persistent, CacheKind::User.persistence());
+    /// assert_eq!(Persistence::Volatile, CacheKind::Memory.persistence());
+    /// assert!(!CacheKind::Memory.persistence().survives_reboot());
+    /// ```
+    pub fn persistence(&self) -> Persistence {
+        match *self {
+            CacheKind::App | CacheKind::User | CacheKind::System => Persistence::Persistent,
+            CacheKind::Tmp                                        => Persistence::Temporary,
+            CacheKind::Memory                                     => Persistence::Volatile
+        }
+    }
+}
+
+impl fmt::Display for CacheKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            CacheKind::App    => "Application Cache",
+            CacheKind::User   => "User Cache",
+            CacheKind::System => "System Cache",
+            CacheKind::Tmp    => "Tmp Cache",
+            CacheKind::Memory => "Memory Cache"
+        })
+    }
+}
+
+/// Describes how long the data stored in a cache directory is expected to survive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Persistence {
+    /// The data is kept until it is removed(application, user and system-wide caches).
+    Persistent,
+    /// The data survives system restarts, but the system might periodically clean it
+    /// (ex: `/var/tmp` is usually cleaned every 30 days, `/tmp` might be cleaned on every restart).
+    Temporary,
+    /// The data is lost when the system restarts(memory caches).
+    Volatile
+}
+
+impl Persistence {
+    /// Returns `false` only if the data is guaranteed to be lost when the system restarts.
+    pub fn survives_reboot(&self) -> bool {
+        *self != Persistence::Volatile
+    }
+}
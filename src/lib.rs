@@ -4,7 +4,8 @@
 //! the cache directory should be made and also helps with the creation itself.
 //!
 //! The [`CacheDir`] type holds the path to the created cache directory, obtained with the help of
-//! [`CacheDirConfig`], if it succeeded to create the directory.
+//! [`CacheDirConfig`], if it succeeded to create the directory, together with the
+//! [`CacheKind`] that was selected.
 //!
 //! [`CacheDir`] derefs to [`PathBuf`] and implements most of the same traits that [`PathBuf`] does.
 //!
@@ -30,6 +31,7 @@
 //!
 //! [`CacheDir`]: struct.CacheDir.html
 //! [`CacheDirConfig`]: struct.CacheDirConfig.html
+//! [`CacheKind`]: enum.CacheKind.html
 //! [`PathBuf`]: https://doc.rust-lang.org/std/path/struct.PathBuf.html
 //! [`Creating an application cache with app_cache_path(custom path)`]: index.html#creating-an-application-cache-with-app_cache_pathcustom-path
 //! [`Creating an application cache without app_cache_path`]: index.html#creating-an-application-cache-without-app_cache_path
@@ -54,6 +56,7 @@
 //!
 //! let current_dir = current_dir().unwrap();
 //! let app_cache = CacheDirConfig::new("example/path")
+//!                                .nested_cache_name(true) // "example/path" has 2 components
 //!                                .app_cache_path(current_dir.as_path())
 //!                                .get_cache_dir().unwrap();
 //!
@@ -106,25 +109,37 @@
 //! let expected_cache_dir: PathBuf;
 //! #[cfg(any(unix, target_os = "redox"))]
 //! {
-//!     let home_dir = env::home_dir();
+//!     let home_dir = env::var_os("HOME").filter(|path| !path.is_empty()).map(PathBuf::from);
 //!
+//!     // `$XDG_CACHE_HOME` takes precedence over `$HOME/.cache` when it is set
 //!     #[cfg(not(any(target_os = "emscripten", target_os = "macos")))]
 //!     {
-//!         expected_cache_dir = home_dir.unwrap().join(".cache").join("example");
+//!         let cache_home = env::var_os("XDG_CACHE_HOME").filter(|path| !path.is_empty());
+//!         expected_cache_dir = match (cache_home, home_dir) {
+//!             (Some(cache_home), _)  => PathBuf::from(cache_home).join("example"),
+//!             (None, Some(home_dir)) => home_dir.join(".cache").join("example"),
+//!             // Without `$HOME`, the home directory is read from the passwd database,
+//!             // which this example doesn't do
+//!             (None, None)           => return
+//!         };
 //!     }
 //!
 //!     #[cfg(target_os = "emscripten")]
 //!     {
-//!         expected_cache_dir = if home_dir.is_none() {
-//!             PathBuf::from("/var/cache").join("example")
-//!         } else {
-//!             home_dir.unwrap().join(".cache").join("example")
+//!         expected_cache_dir = match home_dir {
+//!             Some(home_dir) => home_dir.join(".cache").join("example"),
+//!             None           => PathBuf::from("/var/cache").join("example")
 //!         };
 //!     }
 //!
 //!     #[cfg(target_os = "macos")]
 //!     {
-//!         expected_cache_dir = home_dir.unwrap().join("Library/Caches").join("example");
+//!         expected_cache_dir = match home_dir {
+//!             Some(home_dir) => home_dir.join("Library/Caches").join("example"),
+//!             // Without `$HOME`, the home directory is read from the passwd database,
+//!             // which this example doesn't do
+//!             None           => return
+//!         };
 //!     }
 //! }
 //!
@@ -182,8 +197,10 @@
 //! let expected_cache_dir: PathBuf;
 //! #[cfg(unix)]
 //! {
-//!     // On Unix, we try `/var/tmp` first, because it is more persistent than `/tmp`
-//!     expected_cache_dir = PathBuf::from("/var/tmp").join("example");
+//!     // On Unix, we try `/var/tmp` first, because it is more persistent than `/tmp`.
+//!     // `/var/tmp` is shared by all the users, so the cache is placed in a per-user directory
+//!     let user_segment = cache_dir.user_segment().unwrap();
+//!     expected_cache_dir = PathBuf::from("/var/tmp").join(user_segment).join("example");
 //! }
 //!
 //! #[cfg(windows)]
@@ -202,14 +219,15 @@
 //! use cachedir::CacheDirConfig;
 //! use std::path::PathBuf;
 //!
-//! let cache_dir = CacheDirConfig::new("example/path")
+//! let cache_dir = CacheDirConfig::new("example")
 //!                                .mem_cache(true)
 //!                                .get_cache_dir().unwrap();
 //!
 //! // In-memory caches are supported only on Linux
-//! if cfg!(target_os = "linux") {
-//!     // We try `/dev/shm` before `/run/shm`
-//!     assert_eq!(PathBuf::from("/dev/shm").join("example/path"),
+//! if cfg!(target_os = "linux") && std::env::var_os("XDG_RUNTIME_DIR").is_none() {
+//!     // We try `$XDG_RUNTIME_DIR`(if it is set) and `/dev/shm` before `/run/shm`
+//!     let user_segment = cache_dir.user_segment().unwrap().to_owned();
+//!     assert_eq!(PathBuf::from("/dev/shm").join(user_segment).join("example"),
 //!                cache_dir.into_path_buf());
 //! }
 //! ```
@@ -221,11 +239,11 @@
 //! use cachedir::CacheDirConfig;
 //! use std::path::PathBuf;
 //!
-//! let short_version = CacheDirConfig::new("example/path")
+//! let short_version = CacheDirConfig::new("example")
 //!                                    .try_all_caches()
 //!                                    .get_cache_dir().unwrap();
 //!
-//! let verbose_version = CacheDirConfig::new("example/path")
+//! let verbose_version = CacheDirConfig::new("example")
 //!                                      .user_cache(true) // Order here
 //!                                      .sys_cache(true)  // does
 //!                                      .tmp_cache(true)  // not
@@ -253,7 +271,7 @@
 //! use std::path::{ Path, PathBuf };
 //! use std::env::current_dir;
 //!
-//! let one_way = CacheDirConfig::new("example/path")
+//! let one_way = CacheDirConfig::new("example")
 //!                              .app_cache(true)
 //!                              .try_all_caches()
 //!                              .get_cache_dir().unwrap();
@@ -267,7 +285,7 @@
 //! let app_cache_path = current_dir().unwrap().join(autocreated_dir);
 //!
 //! // It's OK to use `app_cache(true)` here too
-//! let another_way = CacheDirConfig::new("example/path")
+//! let another_way = CacheDirConfig::new("example")
 //!                                  .app_cache_path(app_cache_path.as_path())
 //!                                  .try_all_caches()
 //!                                  .get_cache_dir().unwrap();
@@ -275,7 +293,7 @@
 //! assert_eq!(one_way, another_way);
 //!
 //! // `app_cache_path` overwrites the default that was set in `app_cache(true)`
-//! let yet_another_way = CacheDirConfig::new("example/path")
+//! let yet_another_way = CacheDirConfig::new("example")
 //!                                      .app_cache(true)
 //!                                      .app_cache_path("/tmp/other/path")
 //!                                      .try_all_caches()
@@ -284,7 +302,7 @@
 //! assert!(another_way != yet_another_way);
 //!
 //! // `app_cache(true)` does not overwrite the path of `app_cache_path` if it was set
-//! let or_this_way = CacheDirConfig::new("example/path")
+//! let or_this_way = CacheDirConfig::new("example")
 //!                                  .app_cache_path("/tmp/other/path")
 //!                                  .app_cache(true)
 //!                                  .try_all_caches()
@@ -307,9 +325,33 @@
 //!
 //! [`To the top ⤴`]
 
-use std::path;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(windows)]
+extern crate winapi;
+#[cfg(feature = "blob-store")]
+extern crate sha2;
+
+use std::fs;
 use std::io;
-use std::ffi::OsStr;
+use std::path;
+use std::sync::Arc;
+use std::time::Duration;
+use std::ffi::{ OsStr, OsString };
+
+pub use atomic::AtomicFile;
+#[cfg(feature = "blob-store")]
+pub use blobs::{ BlobReader, Digest };
+pub use cache_name::CacheNameError;
+pub use candidate::{ CacheLocation, Candidate, CandidateStatus };
+pub use cleanup::CleanupReport;
+pub use environment::{ CustomEnvironment, Environment, KnownFolder, ProcessEnvironment };
+pub use error::{ Attempt, CacheDirError };
+pub use fs_info::{ FsInfo, FsType };
+pub use identity::AppIdentity;
+pub use kind::{ CacheKind, Persistence };
+pub use locking::{ CacheLock, LockMode };
+pub use target_os::TargetOs;
 
 // Contains the os-agnostic `create_cache_dir` function
 mod sys_cache;
@@ -317,6 +359,49 @@ mod sys_cache;
 // Traits implementations for `CacheDir`
 mod traits_impls;
 
+// Reading and writing of the `CACHEDIR.TAG` file
+mod cachedir_tag;
+
+// Validation of the cache names
+mod cache_name;
+
+// `Candidate`, `CandidateStatus` and `CacheLocation` - the results of a dry-run
+mod candidate;
+
+// `CacheDirError` and the attempts it is made of
+mod error;
+
+// `CacheKind` and `Persistence`
+mod kind;
+
+// `FsInfo` - the type and the free space of the file system(via `statfs`)
+mod fs_info;
+
+// Size tracking, eviction and expiry of the cache contents
+mod cleanup;
+
+// `AtomicFile` - writing files through a temporary file that is renamed into place
+mod atomic;
+
+// The key-value entries - keys are hashed into sharded file names
+mod entries;
+
+// `CacheLock` - cross-process locks on the cache directory and on the entries
+mod locking;
+
+// `TargetOs` - the operating systems whose cache conventions are known
+mod target_os;
+
+// `AppIdentity` - the platform-idiomatic cache names of an application
+mod identity;
+
+// `Environment` - the home directory, the environment variables, etc. of the current process
+mod environment;
+
+// `Digest` and `BlobReader` - the content-addressed blob store(the `blob-store` feature)
+#[cfg(feature = "blob-store")]
+mod blobs;
+
 /// This structure holds the [`PathBuf`] returned from [`CacheDirConfig`].
 ///
 /// It derefs to [`PathBuf`] and implements most of the same traits as [`PathBuf`].
@@ -347,9 +432,15 @@ mod traits_impls;
 ///
 /// [`CacheDirConfig`]: struct.CacheDirConfig.html
 /// [`PathBuf`]: https://doc.rust-lang.org/std/path/struct.PathBuf.html
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+// `PartialEq`, `Eq`, `Hash`, `PartialOrd` and `Ord` are implemented on the path only
+// (in `traits_impls.rs`), as required by the `Borrow` implementations
+#[derive(Debug, Clone)]
 pub struct CacheDir {
-    path: path::PathBuf
+    path:         path::PathBuf,
+    kind:         CacheKind,
+    user_segment: Option<OsString>,
+    fs_info:      Option<FsInfo>,
+    size_limit:   Option<u64>
 }
 
 impl CacheDir {
@@ -363,6 +454,537 @@ impl CacheDir {
     pub fn into_path_buf(self) -> path::PathBuf {
         self.path
     }
+
+    /// Returns the kind of cache that was selected by `CacheDirConfig`.
+    ///
+    /// ```
+    /// # use cachedir::{ CacheDirConfig, CacheKind };
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .tmp_cache(true)
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// assert_eq!(CacheKind::Tmp, cache_dir.kind());
+    /// ```
+    pub fn kind(&self) -> CacheKind {
+        self.kind
+    }
+
+    /// Describes how long the data stored in the cache directory is expected to survive.
+    ///
+    /// This is a shortcut for `cache_dir.kind().persistence()`.
+    ///
+    /// ```
+    /// # use cachedir::{ CacheDirConfig, Persistence };
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .try_all_caches()
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// if cache_dir.persistence() == Persistence::Volatile {
+    ///     println!("The cache will be lost when the system restarts");
+    /// }
+    /// ```
+    pub fn persistence(&self) -> Persistence {
+        self.kind.persistence()
+    }
+
+    /// Returns the per-user segment that was inserted between the shared parent directory
+    /// and the cache name(ex: the `1000` from `/var/tmp/1000/example`), if there is one.
+    ///
+    /// Read [`per_user`] documentation if you want to find more about per-user caches.
+    ///
+    /// ```
+    /// # use cachedir::{ CacheDirConfig, CacheKind };
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .tmp_cache(true)
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// if let Some(user_segment) = cache_dir.user_segment() {
+    ///     assert!(cache_dir.ends_with(std::path::Path::new(user_segment).join("example")));
+    /// }
+    /// ```
+    ///
+    /// [`per_user`]: struct.CacheDirConfig.html#method.per_user
+    pub fn user_segment(&self) -> Option<&OsStr> {
+        self.user_segment.as_deref()
+    }
+
+    /// Returns the type and the free space of the file system on which the cache directory
+    /// is stored, as they were when the cache directory was selected.
+    ///
+    /// This is known only for memory caches(read [`mem_cache`]) and when a minimum free space
+    /// was required(read [`min_free_space`]). If the parent cache directory had to be created,
+    /// its closest existing ancestor was checked.
+    ///
+    /// ```no_run
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .mem_cache(true)
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// if let Some(fs_info) = cache_dir.fs_info() {
+    ///     println!("{} with {} free bytes", fs_info.fs_type(), fs_info.free_space());
+    /// }
+    /// ```
+    ///
+    /// [`mem_cache`]: struct.CacheDirConfig.html#method.mem_cache
+    /// [`min_free_space`]: struct.CacheDirConfig.html#method.min_free_space
+    pub fn fs_info(&self) -> Option<FsInfo> {
+        self.fs_info
+    }
+
+    /// Returns the total size(in bytes) of the regular files stored in the cache directory
+    /// and in its subdirectories.
+    ///
+    /// Symbolic links are not followed and the `CACHEDIR.TAG` file, the lock files(of the cache
+    /// directory and of the existing entries) and the temporary files of the atomic writes
+    /// that are in progress(read [`create_atomic`]) are not counted.
+    ///
+    /// # Errors
+    /// If the cache directory could not be read. The subdirectories that could not be read
+    /// are ignored.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// println!("The cache uses {} bytes", cache_dir.size().unwrap());
+    /// ```
+    ///
+    /// [`create_atomic`]: struct.CacheDir.html#method.create_atomic
+    pub fn size(&self) -> io::Result<u64> {
+        cleanup::size(&self.path)
+    }
+
+    /// Returns the size limit that was set with [`size_limit`].
+    ///
+    /// [`size_limit`]: struct.CacheDirConfig.html#method.size_limit
+    pub fn size_limit(&self) -> Option<u64> {
+        self.size_limit
+    }
+
+    /// Removes the least recently used files from the cache directory until the total size
+    /// of the cache is not larger than the limit set with [`size_limit`].
+    ///
+    /// A file was used more recently if its access time, or its modification time, is more
+    /// recent(the access time is not updated on the file systems mounted with `noatime`).<br/>
+    /// Symbolic links are not followed, the empty subdirectories are left in place and
+    /// the `CACHEDIR.TAG` file and the lock of the cache directory(read [`lock_exclusive`])
+    /// are never removed. An entry is removed together with its lock file(read
+    /// [`lock_entry`]), unless another process holds the lock, in which case it is kept.<br/>
+    /// The temporary files of the atomic writes(read [`create_atomic`]) are removed only if they
+    /// were not modified for a day, because they are probably still being written otherwise.
+    ///
+    /// The limit is not enforced automatically, so this should be called when it is
+    /// convenient(ex: at startup or after adding files to the cache). If a size limit
+    /// was not set, nothing is removed.
+    ///
+    /// # Errors
+    /// If the cache directory could not be read. The files that could not be removed are
+    /// recorded in the returned [`CleanupReport`].
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .size_limit(100 * 1024 * 1024)
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// let report = cache_dir.enforce_limit().unwrap();
+    /// for path in report.removed() {
+    ///     println!("Removed {}", path.display());
+    /// }
+    /// assert!(report.remaining_size() <= 100 * 1024 * 1024);
+    /// ```
+    ///
+    /// [`size_limit`]: struct.CacheDirConfig.html#method.size_limit
+    /// [`CleanupReport`]: struct.CleanupReport.html
+    /// [`lock_exclusive`]: struct.CacheDir.html#method.lock_exclusive
+    /// [`lock_entry`]: struct.CacheDir.html#method.lock_entry
+    /// [`create_atomic`]: struct.CacheDir.html#method.create_atomic
+    pub fn enforce_limit(&self) -> io::Result<CleanupReport> {
+        cleanup::enforce_limit(&self.path, self.size_limit.unwrap_or(u64::MAX))
+    }
+
+    /// Removes the files that were not used for at least `older_than` and the empty
+    /// subdirectories from the cache directory.
+    ///
+    /// A file was last used at the latest of its access and modification times.<br/>
+    /// Symbolic links are never followed(so nothing outside of the cache directory is removed)
+    /// and they are left in place, together with the `CACHEDIR.TAG` file and the lock of
+    /// the cache directory. The entries are removed like in [`enforce_limit`], together with
+    /// their lock files, unless another process holds the lock, and the temporary files of
+    /// the atomic writes are removed only if they were not modified for a day.
+    ///
+    /// # Errors
+    /// If the cache directory could not be read. The files and the subdirectories that could
+    /// not be removed are recorded in the returned [`CleanupReport`].
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// use std::time::Duration;
+    ///
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// // Removes the files that were not used in the last 30 days
+    /// let report = cache_dir.prune(Duration::from_secs(30 * 24 * 60 * 60)).unwrap();
+    /// println!("Removed {} files({} bytes), {} errors",
+    ///          report.files_removed(), report.bytes_freed(), report.errors().len());
+    /// ```
+    ///
+    /// [`CleanupReport`]: struct.CleanupReport.html
+    /// [`enforce_limit`]: struct.CacheDir.html#method.enforce_limit
+    pub fn prune(&self, older_than: Duration) -> io::Result<CleanupReport> {
+        cleanup::prune(&self.path, older_than)
+    }
+
+    /// Returns the path of the file that stores the entry of `key`.
+    ///
+    /// The key is hashed(128-bit FNV-1a) and the hex encoded hash is split into
+    /// a subdirectory and a file name(ex: `<cache_dir>/3f/a81c...`), so any key is mapped
+    /// to a safe file name and a single directory doesn't end up with too many files.<br/>
+    /// The file starts with the key, followed by the contents of the entry. The hash is not
+    /// cryptographic, so two keys could share the same file and replace each other's entry,
+    /// but [`get`] never returns the contents of another key.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// let path = cache_dir.entry_path("https://example.com/index.html");
+    /// assert!(path.starts_with(&*cache_dir));
+    /// ```
+    ///
+    /// [`get`]: struct.CacheDir.html#method.get
+    pub fn entry_path<K: AsRef<[u8]>>(&self, key: K) -> path::PathBuf {
+        entries::entry_path(&self.path, key.as_ref())
+    }
+
+    /// Returns the contents of the entry of `key`, or `None` if there is no such entry.
+    ///
+    /// Read [`entry_path`] documentation if you want to find more about how the entries
+    /// are stored.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// match cache_dir.get("key").unwrap() {
+    ///     Some(contents) => println!("Cache hit: {} bytes", contents.len()),
+    ///     None           => println!("Cache miss")
+    /// }
+    /// ```
+    ///
+    /// [`entry_path`]: struct.CacheDir.html#method.entry_path
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> io::Result<Option<Vec<u8>>> {
+        entries::get(&self.path, key.as_ref())
+    }
+
+    /// Stores `contents` as the entry of `key`, replacing the previous entry.
+    ///
+    /// The contents are written into a temporary file that is renamed into place, so the other
+    /// threads and processes never read a partially written entry.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// cache_dir.put("key", b"value").unwrap();
+    /// assert_eq!(Some(b"value".to_vec()), cache_dir.get("key").unwrap());
+    /// ```
+    pub fn put<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, contents: V) -> io::Result<()> {
+        entries::put(&self.path, key.as_ref(), contents.as_ref())
+    }
+
+    /// Removes the entry of `key`. Returns `false` if there was no such entry.
+    ///
+    /// The lock file of the entry(read [`lock_entry`]) is removed too, unless a lock is held
+    /// on it(including by the caller), in which case it is left in place.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// cache_dir.put("removed_key", b"value").unwrap();
+    /// assert!(cache_dir.remove("removed_key").unwrap());
+    /// assert!(!cache_dir.remove("removed_key").unwrap());
+    /// ```
+    ///
+    /// [`lock_entry`]: struct.CacheDir.html#method.lock_entry
+    pub fn remove<K: AsRef<[u8]>>(&self, key: K) -> io::Result<bool> {
+        entries::remove(&self.path, key.as_ref())
+    }
+
+    /// Returns `true` if there is an entry for `key`.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// if !cache_dir.contains("key").unwrap() {
+    ///     cache_dir.put("key", b"value").unwrap();
+    /// }
+    /// ```
+    pub fn contains<K: AsRef<[u8]>>(&self, key: K) -> io::Result<bool> {
+        entries::contains(&self.path, key.as_ref())
+    }
+
+    /// Writes `contents` into the `relative_path` file from the cache directory, atomically.
+    ///
+    /// The contents are written into a temporary file from the same directory, flushed to
+    /// the disk and renamed into place, so the other threads and processes observe either
+    /// the previous file or the new one, never a partially written file.<br/>
+    /// The missing parent directories are created.
+    ///
+    /// # Errors
+    /// If `relative_path` could escape the cache directory(the same rules as for the cache
+    /// names apply, read [`CacheDirConfig::new`]), an `InvalidInput` error is returned.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// cache_dir.write_atomic("index/data.json", b"{}").unwrap();
+    /// assert!(cache_dir.write_atomic("../outside.json", b"{}").is_err());
+    /// ```
+    ///
+    /// [`CacheDirConfig::new`]: struct.CacheDirConfig.html#method.new
+    pub fn write_atomic<P, C>(&self, relative_path: P, contents: C) -> io::Result<()>
+        where P: AsRef<path::Path>,
+              C: AsRef<[u8]>
+    {
+        atomic::write(&self.file_path(relative_path.as_ref())?, contents.as_ref())
+    }
+
+    /// Creates an [`AtomicFile`] for the `relative_path` file from the cache directory, which
+    /// can be written gradually and is moved into place only when it is committed.
+    ///
+    /// The missing parent directories are created.
+    ///
+    /// # Errors
+    /// If `relative_path` could escape the cache directory(the same rules as for the cache
+    /// names apply, read [`CacheDirConfig::new`]), an `InvalidInput` error is returned.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// use std::io::Write;
+    ///
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// let mut file = cache_dir.create_atomic("download.bin").unwrap();
+    /// file.write_all(b"partial contents").unwrap();
+    ///
+    /// // Dropping the file without committing it keeps the previous file
+    /// drop(file);
+    /// ```
+    ///
+    /// [`AtomicFile`]: struct.AtomicFile.html
+    /// [`CacheDirConfig::new`]: struct.CacheDirConfig.html#method.new
+    pub fn create_atomic<P: AsRef<path::Path>>(&self, relative_path: P) -> io::Result<AtomicFile> {
+        AtomicFile::new(self.file_path(relative_path.as_ref())?)
+    }
+
+    /// Locks the whole cache directory exclusively, waiting until the other processes
+    /// release their locks.
+    ///
+    /// The lock is held on the `.cachedir.lock` file from the cache directory and it is
+    /// released when the returned [`CacheLock`] is dropped. The locks are advisory, so they
+    /// only coordinate the processes that use them(ex: several tools that share the same
+    /// cache and that should not populate it while another one is cleaning it up).
+    ///
+    /// The lock files are never removed by [`enforce_limit`] and [`prune`].
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// use std::time::Duration;
+    ///
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// let _lock = cache_dir.lock_exclusive().unwrap();
+    /// cache_dir.prune(Duration::from_secs(30 * 24 * 60 * 60)).unwrap();
+    /// ```
+    ///
+    /// [`CacheLock`]: struct.CacheLock.html
+    /// [`enforce_limit`]: struct.CacheDir.html#method.enforce_limit
+    /// [`prune`]: struct.CacheDir.html#method.prune
+    pub fn lock_exclusive(&self) -> io::Result<CacheLock> {
+        locking::lock(self.path.join(locking::DIR_LOCK_NAME), LockMode::Exclusive)
+    }
+
+    /// Locks the whole cache directory in shared mode(other processes can hold shared locks
+    /// at the same time), waiting until an exclusive lock is released.
+    ///
+    /// Read [`lock_exclusive`] documentation if you want to find more about the locks.
+    ///
+    /// [`lock_exclusive`]: struct.CacheDir.html#method.lock_exclusive
+    pub fn lock_shared(&self) -> io::Result<CacheLock> {
+        locking::lock(self.path.join(locking::DIR_LOCK_NAME), LockMode::Shared)
+    }
+
+    /// Same as [`lock_exclusive`], but returns `None` instead of waiting if the cache directory
+    /// is already locked.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// match cache_dir.try_lock_exclusive().unwrap() {
+    ///     Some(_lock) => println!("Cleaning up the cache"),
+    ///     None        => println!("The cache is in use, skipping the cleanup")
+    /// }
+    /// ```
+    ///
+    /// [`lock_exclusive`]: struct.CacheDir.html#method.lock_exclusive
+    pub fn try_lock_exclusive(&self) -> io::Result<Option<CacheLock>> {
+        locking::try_lock(self.path.join(locking::DIR_LOCK_NAME), LockMode::Exclusive)
+    }
+
+    /// Same as [`lock_shared`], but returns `None` instead of waiting if the cache directory
+    /// is locked exclusively.
+    ///
+    /// [`lock_shared`]: struct.CacheDir.html#method.lock_shared
+    pub fn try_lock_shared(&self) -> io::Result<Option<CacheLock>> {
+        locking::try_lock(self.path.join(locking::DIR_LOCK_NAME), LockMode::Shared)
+    }
+
+    /// Locks the entry of `key`(read [`entry_path`]), waiting until the other processes
+    /// release their locks on it.
+    ///
+    /// Every entry has its own lock file(`ab/.cdef....lock` for the `ab/cdef...` entry),
+    /// so the locks of different entries don't block each other. The entry locks are
+    /// independent of the lock of the whole cache directory.<br/>
+    /// The lock file is removed when the entry is removed([`remove`], [`enforce_limit`] or
+    /// [`prune`]) while nobody holds the lock. The processes that were waiting for the lock
+    /// on the removed file lock the new lock file instead, so they still exclude each other.
+    ///
+    /// ```
+    /// # use cachedir::{ CacheDirConfig, LockMode };
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// // Only one process computes the value
+    /// let _lock = cache_dir.lock_entry("key", LockMode::Exclusive).unwrap();
+    /// if !cache_dir.contains("key").unwrap() {
+    ///     cache_dir.put("key", b"expensive value").unwrap();
+    /// }
+    /// ```
+    ///
+    /// [`entry_path`]: struct.CacheDir.html#method.entry_path
+    /// [`remove`]: struct.CacheDir.html#method.remove
+    /// [`enforce_limit`]: struct.CacheDir.html#method.enforce_limit
+    /// [`prune`]: struct.CacheDir.html#method.prune
+    pub fn lock_entry<K: AsRef<[u8]>>(&self, key: K, mode: LockMode) -> io::Result<CacheLock> {
+        locking::lock(locking::entry_lock_path(&self.entry_path(key)), mode)
+    }
+
+    /// Same as [`lock_entry`], but returns `None` instead of waiting if the entry is locked.
+    ///
+    /// [`lock_entry`]: struct.CacheDir.html#method.lock_entry
+    pub fn try_lock_entry<K>(&self, key: K, mode: LockMode) -> io::Result<Option<CacheLock>>
+        where K: AsRef<[u8]>
+    {
+        locking::try_lock(locking::entry_lock_path(&self.entry_path(key)), mode)
+    }
+
+    /// Stores the contents read from `reader` as a blob, addressed by their SHA-256 hash.
+    ///
+    /// The contents are hashed while they are streamed into a temporary file, which is then
+    /// moved to `blobs/sha256/<xx>/<hash>`(where `<xx>` are the first 2 characters of the hash).
+    /// If a blob with the same contents is already stored, the temporary file is removed, so
+    /// identical contents are stored only once.<br/>
+    /// The returned [`Digest`] is used to read the blob back with [`open_blob`].
+    ///
+    /// Available only with the `blob-store` feature.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// use std::io::Read;
+    ///
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// let digest = cache_dir.store_blob(&b"build artifact"[..]).unwrap();
+    ///
+    /// let mut contents = Vec::new();
+    /// cache_dir.open_blob(&digest).unwrap().read_to_end(&mut contents).unwrap();
+    /// assert_eq!(b"build artifact".to_vec(), contents);
+    /// ```
+    ///
+    /// [`Digest`]: struct.Digest.html
+    /// [`open_blob`]: struct.CacheDir.html#method.open_blob
+    #[cfg(feature = "blob-store")]
+    pub fn store_blob<R: io::Read>(&self, reader: R) -> io::Result<Digest> {
+        blobs::store(&self.path, reader)
+    }
+
+    /// Opens the blob with the `digest` hash for reading.
+    ///
+    /// The returned [`BlobReader`] verifies the contents while they are read: if they don't
+    /// match `digest`, reading the end of the blob fails with an `InvalidData` error.
+    ///
+    /// Available only with the `blob-store` feature.
+    ///
+    /// # Errors
+    /// If there is no such blob(a `NotFound` error) or if it could not be opened.
+    ///
+    /// [`BlobReader`]: struct.BlobReader.html
+    #[cfg(feature = "blob-store")]
+    pub fn open_blob(&self, digest: &Digest) -> io::Result<BlobReader> {
+        blobs::open(&self.path, digest)
+    }
+
+    /// Returns the path of the file that stores the blob with the `digest` hash.
+    ///
+    /// Read [`store_blob`] documentation if you want to find more about how the blobs
+    /// are stored.
+    ///
+    /// Available only with the `blob-store` feature.
+    ///
+    /// [`store_blob`]: struct.CacheDir.html#method.store_blob
+    #[cfg(feature = "blob-store")]
+    pub fn blob_path(&self, digest: &Digest) -> path::PathBuf {
+        blobs::blob_path(&self.path, digest)
+    }
+
+    // Validates `relative_path` and creates its missing parent directories
+    fn file_path(&self, relative_path: &path::Path) -> io::Result<path::PathBuf> {
+        if let Err(err) = cache_name::validate(relative_path, true) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, err));
+        }
+
+        let path = self.path.join(relative_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+
+    /// Returns `true` if the cache directory contains a valid `CACHEDIR.TAG` file.
+    ///
+    /// Read [`cachedir_tag`] documentation if you want to find more about cache directory tags.
+    ///
+    /// ```
+    /// # use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("example")
+    ///                                .cachedir_tag(true)
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// assert!(cache_dir.is_tagged());
+    /// ```
+    ///
+    /// [`cachedir_tag`]: struct.CacheDirConfig.html#method.cachedir_tag
+    pub fn is_tagged(&self) -> bool {
+        cachedir_tag::is_tagged(&self.path)
+    }
 }
 
 /// This structure helps configure the desired behavior when attempting to create
@@ -382,7 +1004,8 @@ impl CacheDir {
 /// [`sys_cache`], [`tmp_cache`], [`mem_cache`] and [`try_all_caches`] functions to configure
 /// the behavior.
 ///
-/// The order of attempts looks like this(note that unset cache options are skipped):
+/// The default order of attempts looks like this(note that unset cache options are skipped).
+/// It can be changed with [`order`]:
 ///
 /// 1. Application cache
 ///
@@ -411,6 +1034,7 @@ impl CacheDir {
 /// [`mem_cache`]: struct.CacheDirConfig.html#method.mem_cache
 /// [`mem_cache(true)`]: struct.CacheDirConfig.html#method.mem_cache
 /// [`try_all_caches`]: struct.CacheDirConfig.html#method.try_all_caches
+/// [`order`]: struct.CacheDirConfig.html#method.order
 ///
 /// # Examples
 /// ```
@@ -419,19 +1043,33 @@ impl CacheDir {
 /// ```
 /// This will attempt to create the cache directory `example` in the `User Cache`.<br/>
 /// Read [`user_cache`] documentation if you want to find more about the paths used for `User Cache`.
-pub struct CacheDirConfig<'a, 'b> {
-    cache_name:     &'a path::Path,
-    app_cache_path: Option<&'b path::Path>,
+#[derive(Debug, Clone)]
+pub struct CacheDirConfig {
+    cache_name:     path::PathBuf,
+    app_cache_path: Option<path::PathBuf>,
+    // `true` if the application cache uses the default directory of the target OS,
+    // because `app_cache(true)` was called without `app_cache_path`
+    app_default:    bool,
+    nested_names:   bool,
     app_cache:      bool,
     // wasted an hour on this, obsessing about "user" not being 3 characters aligned,
     // but "usr" is not very clear(for non-Unix users) and does not sound as well when pronouncing it
     user_cache:     bool,
     sys_cache:      bool,
     tmp_cache:      bool,
-    mem_cache:      bool
+    mem_cache:      bool,
+    mem_ram_only:   bool,
+    mem_free_space: u64,
+    min_free_space: u64,
+    order:          Vec<CacheKind>,
+    per_user:       Vec<CacheKind>,
+    probe_writable: bool,
+    size_limit:     Option<u64>,
+    cachedir_tag:   bool,
+    environment:    Arc<dyn Environment>
 }
 
-impl<'a, 'b> CacheDirConfig<'a, 'b> {
+impl CacheDirConfig {
     /// `cache_name` accepts a path - used to create the cache directory.
     ///
     /// If it *does not exist* at the desired location, `CacheDirConfig` will create it when
@@ -440,25 +1078,116 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// If it *already exists* and it is a directory, `get_cache_dir()` will
     /// return the path to the final cache destination(**note:** in this situation, `CacheDirConfig`
     /// cannot guarantee that you have access to write in the final destination, it just confirms
-    /// that the cache directory already exists).
+    /// that the cache directory already exists, unless [`probe_writable(true)`] is used).
+    ///
+    /// `cache_name` is validated when calling `get_cache_dir()`, so it can not escape the parent
+    /// cache directory. These names are rejected with a [`CacheNameError`]:
+    ///
+    /// - Empty names
+    ///
+    /// - Names that contain `..` components
+    ///
+    /// - Absolute paths(ex: `/tmp/x` or `C:\x`)
+    ///
+    /// - Names that contain components reserved on Windows(ex: `CON`, `NUL.txt`, `COM0`,
+    ///   `CONOUT$`)
+    ///
+    /// - Names with multiple components(ex: `org/app`), unless they are allowed with
+    ///   [`nested_cache_name(true)`]
+    ///
+    /// `CacheDirConfig` keeps its own copy of `cache_name`(and of `app_cache_path`), so it can be
+    /// built from a temporary value, stored in other structures, cloned or sent to another thread.
     ///
     /// # Examples
     /// ```
     /// use cachedir::CacheDirConfig;
-    /// let cache_config = CacheDirConfig::new("some/path");
+    /// let cache_config = CacheDirConfig::new("some_cache");
+    ///
+    /// let cache_name = format!("{}-{}", "some", "name");
+    /// let cache_config = CacheDirConfig::new(&cache_name);
     /// ```
-    pub fn new<S: AsRef<OsStr> + ?Sized>(cache_name: &'a S) -> CacheDirConfig<'a, 'b> {
+    ///
+    /// [`CacheNameError`]: enum.CacheNameError.html
+    /// [`nested_cache_name(true)`]: struct.CacheDirConfig.html#method.nested_cache_name
+    /// [`probe_writable(true)`]: struct.CacheDirConfig.html#method.probe_writable
+    pub fn new<S: AsRef<OsStr> + ?Sized>(cache_name: &S) -> CacheDirConfig {
         CacheDirConfig {
-            cache_name:     path::Path::new(cache_name),
+            cache_name:     path::PathBuf::from(cache_name),
             app_cache_path: None,
+            app_default:    false,
+            nested_names:   false,
             app_cache:      false,
             user_cache:     false,
             sys_cache:      false,
             tmp_cache:      false,
-            mem_cache:      false
+            mem_cache:      false,
+            mem_ram_only:   false,
+            mem_free_space: 0,
+            min_free_space: 0,
+            order:          Vec::new(),
+            per_user:       vec![CacheKind::Tmp, CacheKind::Memory],
+            probe_writable: false,
+            size_limit:     None,
+            cachedir_tag:   false,
+            environment:    Arc::new(ProcessEnvironment)
         }
     }
 
+    /// Same as [`new`], but the cache name is derived from `identity`, following
+    /// the conventions of the current OS(ex: `com.Org.App` on macOS, `org/app` on Linux and
+    /// `Org\App\Cache` on Windows).
+    ///
+    /// Nested cache names are allowed(read [`nested_cache_name`]), because on most systems
+    /// the name has multiple components.
+    ///
+    /// Read [`AppIdentity`] documentation if you want to find more about the names.
+    ///
+    /// # Examples
+    /// ```
+    /// use cachedir::{ AppIdentity, CacheDirConfig, TargetOs };
+    ///
+    /// let identity = AppIdentity::new("com", "Example", "App");
+    /// let cache_dir = CacheDirConfig::from_app_identity(&identity)
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// assert!(cache_dir.ends_with(identity.cache_name(TargetOs::current())));
+    /// ```
+    ///
+    /// [`new`]: struct.CacheDirConfig.html#method.new
+    /// [`nested_cache_name`]: struct.CacheDirConfig.html#method.nested_cache_name
+    /// [`AppIdentity`]: struct.AppIdentity.html
+    pub fn from_app_identity(identity: &AppIdentity) -> CacheDirConfig {
+        let mut cache_config = CacheDirConfig::new(&identity.cache_name(TargetOs::current()));
+        cache_config.nested_names = true;
+        cache_config
+    }
+
+    /// This function tells `CacheDirConfig` if `cache_name` is allowed to have multiple
+    /// components(ex: `org/app`), in which case all the directories will be created.
+    ///
+    /// Even when nested cache names are allowed, the other validation rules described
+    /// in [`new`] still apply.
+    ///
+    /// The default is `false`.
+    ///
+    /// # Examples
+    /// ```
+    /// use cachedir::CacheDirConfig;
+    ///
+    /// let cache_dir = CacheDirConfig::new("org/app")
+    ///                                .nested_cache_name(true)
+    ///                                .get_cache_dir().unwrap();
+    /// assert!(cache_dir.ends_with("org/app"));
+    ///
+    /// assert!(CacheDirConfig::new("org/app").get_cache_dir().is_err());
+    /// ```
+    ///
+    /// [`new`]: struct.CacheDirConfig.html#method.new
+    pub fn nested_cache_name(&mut self, value: bool) -> &mut CacheDirConfig {
+        self.nested_names = value;
+        self
+    }
+
     /// This function allows to choose a custom path where the cache directory should be created.
     ///
     /// If it *does not exist*, `CacheDirConfig` will attempt to create it.
@@ -472,13 +1201,12 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// # Examples
     /// ```no_run
     /// use cachedir::CacheDirConfig;
-    /// let cache_dir = CacheDirConfig::new("some/path")
+    /// let cache_dir = CacheDirConfig::new("some_cache")
     ///                                .app_cache_path("/application/cache")
     ///                                .get_cache_dir();
     /// ```
-    pub fn app_cache_path<S: AsRef<OsStr> + ?Sized>(&mut self,
-                                                    path: &'b S) -> &mut CacheDirConfig<'a, 'b> {
-        self.app_cache_path = Some(path::Path::new(path));
+    pub fn app_cache_path<S: AsRef<OsStr> + ?Sized>(&mut self, path: &S) -> &mut CacheDirConfig {
+        self.app_cache_path = Some(path::PathBuf::from(path));
         self.app_cache      = true;
         self
     }
@@ -501,18 +1229,16 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// # Examples
     /// ```
     /// use cachedir::CacheDirConfig;
-    /// let cache_dir = CacheDirConfig::new("some/path")
+    /// let cache_dir = CacheDirConfig::new("some_cache")
     ///                                .app_cache(true)
     ///                                .get_cache_dir();
     /// ```
-    pub fn app_cache(&mut self, value: bool)  -> &mut CacheDirConfig<'a, 'b> {
+    pub fn app_cache(&mut self, value: bool)  -> &mut CacheDirConfig {
         self.app_cache = value;
+        // The default directory depends on the target OS, so it is resolved when computing
+        // the locations(ex: `locations(TargetOs::Windows)` on Linux uses "Cache")
         if self.app_cache_path.is_none() && self.app_cache {
-            self.app_cache_path = if cfg!(not(windows)) {
-                Some(path::Path::new(".cache"))
-            } else {
-                Some(path::Path::new("Cache"))
-            };
+            self.app_default = true;
         }
         self
     }
@@ -524,48 +1250,57 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// is to use the `User Cache`.
     ///
     /// ### Unix
-    /// `CacheDirConfig` will attempt to obtain the `HOME` directory of the user.
+    /// On `non-macOS` systems, `CacheDirConfig` follows the
+    /// [XDG Base Directory Specification](https://specifications.freedesktop.org/basedir-spec/latest/):
+    /// if `XDG_CACHE_HOME` is set to a non-empty value, it is used as the parent directory
+    /// of the final cache destination(and created if it is missing).<br/>
+    /// The specification requires `XDG_CACHE_HOME` to be an absolute path - if it is relative,
+    /// the `User Cache` attempt fails with an `std::io::ErrorKind::InvalidInput` error.
+    ///
+    /// Otherwise, `CacheDirConfig` will attempt to obtain the home directory of the user:
+    /// a non-empty `$HOME`, or the home directory from the passwd entry of the user when
+    /// `$HOME` is not set(ex: in bare-bones containers).
     /// If it fails, it will try the next fallback. If a fallback was not configured, it will
-    /// return an `std::io::Error` when calling `get_cache_dir`.<br/>
+    /// return a `CacheDirError` when calling `get_cache_dir`.<br/>
     /// An exception is made for `Emscripten` - in this case it will attempt to create `/var/cache`
     /// as a parent directory for the final cache destination.
     ///
-    /// If `HOME` was found, than `CacheDirConfig` will attempt to return or
-    /// create(if it is missing) the `.cache` directory from inside the `HOME` directory
+    /// If the home directory was found, than `CacheDirConfig` will attempt to return or
+    /// create(if it is missing) the `.cache` directory from inside the home directory
     /// on `non-macOS` systems and `Library/Caches` on `macOS`.
     ///
     /// ### Windows
     /// `CacheDirConfig` will attempt to create the cache directory inside these paths(and
     /// fallback to the next if it fails):
     ///
-    /// 1. Windows environment variable: `%LOCALAPPDATA%`
-    ///
-    /// 2. `%APPDATA%`
+    /// 1. The `LocalAppData` known folder(obtained with `SHGetKnownFolderPath`)
+    ///    --ifndef--> `%LOCALAPPDATA%`.<br/>
+    ///    The roaming `%APPDATA%` is never used, because it is synced to the domain profiles.
     ///
-    /// 3. Rust's `home_dir` which returns `%HOME%` --ifndef--> `%USERPROFILE%`
-    /// --ifndef--> `OS syscall`.<br/>
-    /// Since the user's home directory is not a dedicated cache directory, `CacheDirConfig`
-    /// will attempt to create the `Cache` directory inside it.
+    /// 2. The profile directory of the user: the `Profile` known folder(obtained with
+    ///    `SHGetKnownFolderPath`) --ifndef--> `%USERPROFILE%`.<br/>
+    ///    Since the user's home directory is not a dedicated cache directory, `CacheDirConfig`
+    ///    will attempt to create the `Cache` directory inside it.
     ///
     /// If it fails, it will try the next fallback. If a fallback was not configured, it will
-    /// return an `std::io::Error` when calling `get_cache_dir`.
+    /// return a `CacheDirError` when calling `get_cache_dir`.
     ///
     /// ### Redox
-    /// `CacheDirConfig` will attempt to obtain the `HOME` directory of the user.
+    /// `CacheDirConfig` will attempt to obtain the home directory of the user, like on Unix.
     /// If it fails, it will try the next fallback. If a fallback was not configured, it will
-    /// return an `std::io::Error` when calling `get_cache_dir`.
+    /// return a `CacheDirError` when calling `get_cache_dir`.
     ///
-    /// If `HOME` was found, than `CacheDirConfig` will attempt to return or
-    /// create(if it is missing) the `.cache` directory from inside the `HOME` directory.
+    /// If the home directory was found, than `CacheDirConfig` will attempt to return or
+    /// create(if it is missing) the `.cache` directory from inside the home directory.
     ///
     /// # Examples
     /// ```
     /// use cachedir::CacheDirConfig;
-    /// let cache_dir = CacheDirConfig::new("some/path")
+    /// let cache_dir = CacheDirConfig::new("some_cache")
     ///                                .user_cache(true)
     ///                                .get_cache_dir();
     /// ```
-    pub fn user_cache(&mut self, value: bool) -> &mut CacheDirConfig<'a, 'b> {
+    pub fn user_cache(&mut self, value: bool) -> &mut CacheDirConfig {
         self.user_cache = value;
         self
     }
@@ -579,21 +1314,22 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// when calling `get_cache_dir`.
     ///
     /// If it fails, it will try the next fallback. If a fallback was not configured, it will
-    /// return an `std::io::Error` when calling `get_cache_dir`.
+    /// return a `CacheDirError` when calling `get_cache_dir`.
     ///
     /// ### Unix(macOS)
     /// `CacheDirConfig` will attempt to create the cache directory inside `/Library/Caches`
     /// when calling `get_cache_dir`.
     ///
     /// If it fails, it will try the next fallback. If a fallback was not configured, it will
-    /// return an `std::io::Error` when calling `get_cache_dir`.
+    /// return a `CacheDirError` when calling `get_cache_dir`.
     ///
     /// ### Windows
-    /// `CacheDirConfig` will attempt to create the cache directory inside `%ProgramData%`
+    /// `CacheDirConfig` will attempt to create the cache directory inside the `ProgramData`
+    /// known folder(obtained with `SHGetKnownFolderPath`) --ifndef--> `%ProgramData%`
     /// when calling `get_cache_dir`.
     ///
     /// If it fails, it will try the next fallback. If a fallback was not configured, it will
-    /// return an `std::io::Error` when calling `get_cache_dir`.
+    /// return a `CacheDirError` when calling `get_cache_dir`.
     ///
     /// ### Redox
     /// Currently not supported
@@ -601,11 +1337,11 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// # Examples
     /// ```no_run
     /// use cachedir::CacheDirConfig;
-    /// let cache_dir = CacheDirConfig::new("some/path")
+    /// let cache_dir = CacheDirConfig::new("some_cache")
     ///                                .sys_cache(true)
     ///                                .get_cache_dir();
     /// ```
-    pub fn sys_cache(&mut self, value: bool)  -> &mut CacheDirConfig<'a, 'b> {
+    pub fn sys_cache(&mut self, value: bool)  -> &mut CacheDirConfig {
         self.sys_cache = value;
         self
     }
@@ -624,8 +1360,13 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// `/var/tmp` is preferred because it is more persistent between system restarts.<br/>
     /// It is usually automatically cleaned every 30 days by the system.
     ///
+    /// Since these directories are shared by all the users, the cache directory is created
+    /// inside a per-user directory(ex: `/var/tmp/1000/some_cache`, read [`per_user`]) with
+    /// the `0700` mode. An existing directory is reused only if it is not a symbolic link and
+    /// if it is owned by the current user, otherwise the location is skipped.
+    ///
     /// If these fail, `CacheDirConfig` will try the next fallback.
-    /// If a fallback was not configured, it will return an `std::io::Error` when calling
+    /// If a fallback was not configured, it will return a `CacheDirError` when calling
     /// `get_cache_dir`.
     ///
     /// ### Windows and Redox
@@ -634,16 +1375,18 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// The `TMP/TEMP` directory is obtained internally by calling Rust's `temp_dir`.
     ///
     /// If it fails, it will try the next fallback. If a fallback was not configured, it
-    /// will return `std::io::Error` when calling `get_cache_dir`.
+    /// will return `CacheDirError` when calling `get_cache_dir`.
     ///
     /// # Examples
     /// ```
     /// use cachedir::CacheDirConfig;
-    /// let cache_dir = CacheDirConfig::new("some/path")
+    /// let cache_dir = CacheDirConfig::new("some_cache")
     ///                                .tmp_cache(true)
     ///                                .get_cache_dir();
     /// ```
-    pub fn tmp_cache(&mut self, value: bool)  -> &mut CacheDirConfig<'a, 'b> {
+    ///
+    /// [`per_user`]: struct.CacheDirConfig.html#method.per_user
+    pub fn tmp_cache(&mut self, value: bool)  -> &mut CacheDirConfig {
         self.tmp_cache = value;
         self
     }
@@ -653,24 +1396,93 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// This function tells `CacheDirConfig` if it should attempt to create a cache directory
     /// inside one of these paths:
     ///
-    /// 1. `/dev/shm`
+    /// 1. `$XDG_RUNTIME_DIR`(Linux only, usually `/run/user/<uid>`)
+    ///
+    /// 2. `/dev/shm`
     ///
-    /// 2. `/run/shm`
+    /// 3. `/run/shm`
     ///
-    /// If these fail, when calling `get_cache_dir`, it will return an `std::io::Error`.
+    /// `$XDG_RUNTIME_DIR` is preferred because it belongs only to the current user. It is used
+    /// only if it is an absolute path and if it is stored on a `tmpfs` or `ramfs` file system,
+    /// otherwise the location is skipped.
+    ///
+    /// Since `/dev/shm` and `/run/shm` are shared by all the users, the cache directory is
+    /// created inside a per-user directory(ex: `/dev/shm/1000/some_cache`, read [`per_user`])
+    /// with the `0700` mode. An existing directory is reused only if it is not a symbolic link
+    /// and if it is owned by the current user, otherwise the location is skipped.
+    ///
+    /// If these fail, when calling `get_cache_dir`, it will return a `CacheDirError`.
     ///
     /// # Examples
     /// ```no_run
     /// use cachedir::CacheDirConfig;
-    /// let cache_dir = CacheDirConfig::new("some/path")
+    /// let cache_dir = CacheDirConfig::new("some_cache")
     ///                                .mem_cache(true)
     ///                                .get_cache_dir();
     /// ```
-    pub fn mem_cache(&mut self, value: bool)  -> &mut CacheDirConfig<'a, 'b> {
+    ///
+    /// [`per_user`]: struct.CacheDirConfig.html#method.per_user
+    pub fn mem_cache(&mut self, value: bool)  -> &mut CacheDirConfig {
         self.mem_cache = value;
         self
     }
 
+    /// This function tells `CacheDirConfig` if the memory cache locations must be stored
+    /// in RAM(on a `tmpfs` or `ramfs` file system).
+    ///
+    /// `/dev/shm` is not always a `tmpfs`(ex: it can be a directory from the disk that was
+    /// bind-mounted in a container). When this is `true`, such locations are skipped.
+    ///
+    /// The type of the file system can only be determined on Linux and Android. On the other
+    /// OSes, the locations can not be verified, so they are all skipped(their status is
+    /// [`CandidateStatus::Unverifiable`]).
+    ///
+    /// The file system of the selected location is returned by [`CacheDir::fs_info`].<br/>
+    /// `$XDG_RUNTIME_DIR` is always required to be stored in RAM.
+    ///
+    /// The default is `false`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .mem_cache(true)
+    ///                                .mem_cache_ram_only(true)
+    ///                                .get_cache_dir();
+    /// ```
+    ///
+    /// [`CacheDir::fs_info`]: struct.CacheDir.html#method.fs_info
+    /// [`CandidateStatus::Unverifiable`]: enum.CandidateStatus.html#variant.Unverifiable
+    pub fn mem_cache_ram_only(&mut self, value: bool) -> &mut CacheDirConfig {
+        self.mem_ram_only = value;
+        self
+    }
+
+    /// This function sets the minimum free space(in bytes) that a memory cache location
+    /// must have, otherwise it is skipped.
+    ///
+    /// A `tmpfs` can be very small(ex: containers usually have a 64MB `/dev/shm`).<br/>
+    /// If [`min_free_space`] is also set, the largest of the two values is required.
+    ///
+    /// The default is `0`(no minimum).
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use cachedir::CacheDirConfig;
+    ///
+    /// // Requires at least 512MB
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .mem_cache(true)
+    ///                                .mem_cache_min_free_space(512 * 1024 * 1024)
+    ///                                .get_cache_dir();
+    /// ```
+    ///
+    /// [`min_free_space`]: struct.CacheDirConfig.html#method.min_free_space
+    pub fn mem_cache_min_free_space(&mut self, bytes: u64) -> &mut CacheDirConfig {
+        self.mem_free_space = bytes;
+        self
+    }
+
     /// This function tells `CacheDirConfig` if it should try to use all the cache fallbacks
     /// when attempting to create the cache directory.
     ///
@@ -681,22 +1493,22 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
     /// use cachedir::CacheDirConfig;
     ///
     /// // This will not activate the `app_cache`
-    /// let cache_dir1 = CacheDirConfig::new("some/path")
+    /// let cache_dir1 = CacheDirConfig::new("some_cache")
     ///                                .try_all_caches()
     ///                                .get_cache_dir();
     ///
     /// // This will activate `app_cache`
-    /// let cache_dir2 = CacheDirConfig::new("some/path")
+    /// let cache_dir2 = CacheDirConfig::new("some_cache")
     ///                                 .app_cache_path("/path/to/app/cache")
     ///                                 .app_cache(false) // try_all_caches will activate it back
     ///                                 .try_all_caches()
     ///                                 .get_cache_dir();
     /// ```
-    pub fn try_all_caches(&mut self) -> &mut CacheDirConfig<'a, 'b> {
+    pub fn try_all_caches(&mut self) -> &mut CacheDirConfig {
         // We don't use the `app_cache`(with its fallback to ".cache" and "Cache") function
         // for this one because we assume that the user prefers system-wide directories and because
         // he might not expect to use an application cache by default, if he didn't ask for it
-        if self.app_cache_path.is_some() { self.app_cache = true };
+        if self.app_cache_path.is_some() || self.app_default { self.app_cache = true };
         self.user_cache = true;
         self.sys_cache  = true;
         self.tmp_cache  = true;
@@ -704,26 +1516,353 @@ impl<'a, 'b> CacheDirConfig<'a, 'b> {
         self
     }
 
+    /// This function sets the minimum free space(in bytes) that the file system of a cache
+    /// location must have.
+    ///
+    /// Every location is checked(with `statvfs` on Unix and `GetDiskFreeSpaceExW` on Windows)
+    /// before creating the cache directory. The locations that don't have enough free space
+    /// are skipped and recorded in the returned [`CacheDirError`], and the next fallback
+    /// is attempted.
+    ///
+    /// The free space is checked only once, when calling `get_cache_dir`, so it is not
+    /// reserved for the cache.<br/>
+    /// The file system that was checked is returned by [`CacheDir::fs_info`].
+    ///
+    /// The default is `0`(no minimum).
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use cachedir::CacheDirConfig;
+    ///
+    /// // Requires at least 4GB, in the user cache or in `/var/tmp`
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .user_cache(true)
+    ///                                .tmp_cache(true)
+    ///                                .min_free_space(4 * 1024 * 1024 * 1024)
+    ///                                .get_cache_dir();
+    /// ```
+    ///
+    /// [`CacheDirError`]: struct.CacheDirError.html
+    /// [`CacheDir::fs_info`]: struct.CacheDir.html#method.fs_info
+    pub fn min_free_space(&mut self, bytes: u64) -> &mut CacheDirConfig {
+        self.min_free_space = bytes;
+        self
+    }
+
+    /// This function sets an explicit priority list for the cache options.
+    ///
+    /// The passed cache options are enabled and will be attempted in the given order.<br/>
+    /// Cache options that are enabled but are missing from the list(ex: with [`try_all_caches`])
+    /// are attempted afterwards, in the default order.
+    ///
+    /// Passing [`CacheKind::App`] is the same as calling [`app_cache(true)`].
+    /// Cache options from the list can still be disabled later by calling, for example,
+    /// `user_cache(false)`.
+    ///
+    /// Calling `order` again replaces the previous priority list(but does not disable
+    /// the cache options that it enabled).
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use cachedir::{ CacheDirConfig, CacheKind };
+    ///
+    /// // Attempts the memory cache first and falls back to the user cache
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .order(&[CacheKind::Memory, CacheKind::User])
+    ///                                .get_cache_dir();
+    ///
+    /// // Attempts the system-wide cache, the user cache, the tmp cache and the memory cache
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .try_all_caches()
+    ///                                .order(&[CacheKind::System, CacheKind::User])
+    ///                                .get_cache_dir();
+    /// ```
+    ///
+    /// [`try_all_caches`]: struct.CacheDirConfig.html#method.try_all_caches
+    /// [`app_cache(true)`]: struct.CacheDirConfig.html#method.app_cache
+    /// [`CacheKind::App`]: enum.CacheKind.html#variant.App
+    pub fn order(&mut self, kinds: &[CacheKind]) -> &mut CacheDirConfig {
+        for kind in kinds {
+            match *kind {
+                CacheKind::App    => { self.app_cache(true); },
+                CacheKind::User   => self.user_cache = true,
+                CacheKind::System => self.sys_cache  = true,
+                CacheKind::Tmp    => self.tmp_cache  = true,
+                CacheKind::Memory => self.mem_cache  = true
+            }
+        }
+        self.order = kinds.to_vec();
+        self
+    }
+
+    /// This function tells `CacheDirConfig` if the cache directory of the `kind` cache option
+    /// should be placed inside a per-user directory, when its parent directory is shared by
+    /// all the users(ex: `/var/tmp/1000/some_cache` instead of `/var/tmp/some_cache`).
+    ///
+    /// This prevents the users of the same system from fighting over(or squatting) the same
+    /// cache directory. The per-user directory is named after the current user:
+    ///
+    /// - Unix: the effective user id(ex: `1000`)
+    ///
+    /// - Windows: the security identifier(SID) of the user(ex: `S-1-5-21-...-1001`)
+    ///
+    /// - Redox: `$USER`
+    ///
+    /// The per-user directory is used only by the cache locations that are shared: `/var/tmp`,
+    /// `/tmp`, `/dev/shm` and `/run/shm` on Unix and the system-wide cache on every OS(the user
+    /// and application caches are never affected). If the current user can not be identified,
+    /// the location is skipped.
+    ///
+    /// The default is `true` for [`CacheKind::Tmp`] and [`CacheKind::Memory`] and `false`
+    /// for [`CacheKind::System`].<br/>
+    /// The segment that was used is returned by [`CacheDir::user_segment`].
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use cachedir::{ CacheDirConfig, CacheKind };
+    ///
+    /// // Creates the cache directory in `/var/tmp/some_cache`(not `/var/tmp/1000/some_cache`)
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .tmp_cache(true)
+    ///                                .per_user(CacheKind::Tmp, false)
+    ///                                .get_cache_dir();
+    ///
+    /// // Creates the cache directory in `/var/cache/1000/some_cache`
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .sys_cache(true)
+    ///                                .per_user(CacheKind::System, true)
+    ///                                .get_cache_dir();
+    /// ```
+    ///
+    /// [`CacheKind::Tmp`]: enum.CacheKind.html#variant.Tmp
+    /// [`CacheKind::Memory`]: enum.CacheKind.html#variant.Memory
+    /// [`CacheKind::System`]: enum.CacheKind.html#variant.System
+    /// [`CacheDir::user_segment`]: struct.CacheDir.html#method.user_segment
+    pub fn per_user(&mut self, kind: CacheKind, value: bool) -> &mut CacheDirConfig {
+        self.per_user.retain(|per_user_kind| *per_user_kind != kind);
+        if value {
+            self.per_user.push(kind);
+        }
+        self
+    }
+
+    /// This function tells `CacheDirConfig` if it should check that the cache directory is
+    /// writable before returning it from `get_cache_dir`.
+    ///
+    /// By default, a cache directory that already exists is returned even if it can not be
+    /// written into(ex: it is on a read-only mount or it is owned by another user).<br/>
+    /// When this is `true`, a uniquely named temporary file is created and removed inside
+    /// the cache directory. If that fails, the location is recorded in the returned
+    /// [`CacheDirError`] and the next fallback is attempted.
+    ///
+    /// The default is `false`.
+    ///
+    /// # Examples
+    /// ```
+    /// use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .try_all_caches()
+    ///                                .probe_writable(true)
+    ///                                .get_cache_dir();
+    /// ```
+    ///
+    /// [`CacheDirError`]: struct.CacheDirError.html
+    pub fn probe_writable(&mut self, value: bool) -> &mut CacheDirConfig {
+        self.probe_writable = value;
+        self
+    }
+
+    /// This function sets the maximum size(in bytes) of the cache contents.
+    ///
+    /// The limit is stored in the returned `CacheDir` and it is enforced only when calling
+    /// [`CacheDir::enforce_limit`], which removes the least recently used files.
+    ///
+    /// The default is no limit.
+    ///
+    /// # Examples
+    /// ```
+    /// use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .size_limit(1024 * 1024 * 1024)
+    ///                                .get_cache_dir().unwrap();
+    ///
+    /// let report = cache_dir.enforce_limit().unwrap();
+    /// println!("Freed {} bytes", report.bytes_freed());
+    /// ```
+    ///
+    /// [`CacheDir::enforce_limit`]: struct.CacheDir.html#method.enforce_limit
+    pub fn size_limit(&mut self, bytes: u64) -> &mut CacheDirConfig {
+        self.size_limit = Some(bytes);
+        self
+    }
+
+    /// This function tells `CacheDirConfig` if it should write a `CACHEDIR.TAG` file into
+    /// the cache directory when calling `get_cache_dir`.
+    ///
+    /// Backup and archiving tools(ex: `tar --exclude-caches`, `borg`, `restic`) skip
+    /// the directories that contain a valid [cache directory tag](https://bford.info/cachedir/).
+    ///
+    /// The tag is written atomically(into a temporary file that is renamed into place) when it is
+    /// missing or invalid. An existing valid tag is left untouched.<br/>
+    /// If the tag could not be written, the location is skipped like any other failed attempt
+    /// and the next fallback is attempted.
+    ///
+    /// The default is `false`.
+    ///
+    /// # Examples
+    /// ```
+    /// use cachedir::CacheDirConfig;
+    /// let cache_dir = CacheDirConfig::new("some_cache")
+    ///                                .cachedir_tag(true)
+    ///                                .get_cache_dir();
+    /// ```
+    pub fn cachedir_tag(&mut self, value: bool) -> &mut CacheDirConfig {
+        self.cachedir_tag = value;
+        self
+    }
+
+    /// This function sets the environment from which the cache locations are computed:
+    /// the home directory, the environment variables(ex: `XDG_CACHE_HOME`), the temporary
+    /// directory, the current directory(for [`app_cache_path`]) and the user id(for the
+    /// [`per_user`] caches).
+    ///
+    /// `CacheDirConfig` keeps its own copy of `environment`. The environment is only used
+    /// to compute the locations, the cache directory is still created on the real filesystem.
+    ///
+    /// The default is the [`ProcessEnvironment`].
+    ///
+    /// # Examples
+    /// ```
+    /// use cachedir::{ CacheDirConfig, CustomEnvironment };
+    /// use std::path::Path;
+    ///
+    /// let candidates = CacheDirConfig::new("some_cache")
+    ///                                 .environment(CustomEnvironment::new()
+    ///                                                                .set_home_dir("/home/user")
+    ///                                                                .set_var("XDG_CACHE_HOME",
+    ///                                                                         "/xdg/cache"))
+    ///                                 .candidates().unwrap();
+    ///
+    /// if cfg!(target_os = "linux") {
+    ///     assert_eq!(Path::new("/xdg/cache/some_cache"), candidates[0].path());
+    /// }
+    /// ```
+    ///
+    /// [`app_cache_path`]: struct.CacheDirConfig.html#method.app_cache_path
+    /// [`per_user`]: struct.CacheDirConfig.html#method.per_user
+    /// [`ProcessEnvironment`]: struct.ProcessEnvironment.html
+    pub fn environment<E>(&mut self, environment: &E) -> &mut CacheDirConfig
+        where E: Environment + Clone + 'static
+    {
+        self.environment = Arc::new(environment.clone());
+        self
+    }
+
+    /// This computes the candidate cache directories based on the `CacheDirConfig`
+    /// configurations, **without creating anything**.
+    ///
+    /// The candidates are returned in the order in which `get_cache_dir` would attempt them,
+    /// together with their kind and their current [`CandidateStatus`].<br/>
+    /// The locations that `get_cache_dir` would skip are reported too(ex: the `HOME` directory
+    /// is not known, or `$XDG_RUNTIME_DIR` is not stored in RAM), with a rejected status and
+    /// the reason why they would be skipped.
+    ///
+    /// # Errors
+    /// If `cache_name` is not valid, [`CacheNameError`] is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// use cachedir::CacheDirConfig;
+    ///
+    /// let candidates = CacheDirConfig::new("some_cache")
+    ///                                 .try_all_caches()
+    ///                                 .candidates().unwrap();
+    ///
+    /// for candidate in &candidates {
+    ///     println!("[{}] {}: {:?}",
+    ///              candidate.kind(), candidate.path().display(), candidate.status());
+    /// }
+    /// ```
+    ///
+    /// [`CandidateStatus`]: enum.CandidateStatus.html
+    /// [`CacheNameError`]: enum.CacheNameError.html
+    pub fn candidates(&self) -> Result<Vec<Candidate>, CacheNameError> {
+        sys_cache::candidates(self)
+    }
+
+    /// This computes the cache directories that would be attempted on `target_os`, based on
+    /// the `CacheDirConfig` configurations, **without touching the filesystem**.
+    ///
+    /// The rules of any OS can be used from any OS(ex: a packaging tool running on Linux can
+    /// compute where the Windows build of an application will put its cache). The paths are
+    /// computed from the environment set with [`environment`], which should describe
+    /// the target system(its home directory, its environment variables, etc...), and they use
+    /// the path separators of `target_os`.
+    ///
+    /// The locations are returned in the order in which `get_cache_dir` would attempt them.
+    /// The locations that would be skipped on `target_os`(ex: the home directory is not known)
+    /// are reported too, with their status and reason. The file system checks
+    /// (ex: [`min_free_space`]) are not made.
+    ///
+    /// # Errors
+    /// If `cache_name` is not valid on `target_os`, [`CacheNameError`] is returned(ex: `a\..`
+    /// escapes its parent directory on Windows, while `a\b` is a plain file name on Linux).
+    ///
+    /// # Examples
+    /// ```
+    /// use cachedir::{ AppIdentity, CacheDirConfig, CacheKind, CandidateStatus, CustomEnvironment,
+    ///                 TargetOs };
+    /// use std::path::Path;
+    ///
+    /// let mut windows = CustomEnvironment::new();
+    /// windows.set_var("LOCALAPPDATA", r"C:\Users\me\AppData\Local")
+    ///        .set_var("ProgramData", r"C:\ProgramData");
+    ///
+    /// let identity = AppIdentity::new("com", "Example", "App");
+    /// let locations = CacheDirConfig::new(&identity.cache_name(TargetOs::Windows))
+    ///                                .nested_cache_name(true)
+    ///                                .user_cache(true)
+    ///                                .sys_cache(true)
+    ///                                .environment(&windows)
+    ///                                .locations(TargetOs::Windows).unwrap();
+    ///
+    /// assert_eq!(CacheKind::User, locations[0].kind());
+    /// assert_eq!(Path::new(r"C:\Users\me\AppData\Local\Example\App\Cache"),
+    ///            locations[0].path());
+    ///
+    /// // The fallback to the profile directory is skipped, because it is not known
+    /// assert_eq!(Some(CandidateStatus::Unavailable), locations[1].status());
+    /// assert_eq!(Path::new(r"C:\ProgramData\Example\App\Cache"), locations[2].path());
+    /// ```
+    ///
+    /// [`environment`]: struct.CacheDirConfig.html#method.environment
+    /// [`min_free_space`]: struct.CacheDirConfig.html#method.min_free_space
+    /// [`CacheNameError`]: enum.CacheNameError.html
+    pub fn locations(&self, target_os: TargetOs) -> Result<Vec<CacheLocation>, CacheNameError> {
+        sys_cache::locations(self, target_os)
+    }
+
     /// This creates the cache directory based on the `CacheDirConfig` configurations.
     ///
     /// The returned `CacheDir` contains the path to the cache directory.
     ///
     /// # Errors
-    /// If the directory could not be created, `std::io::Error` is returned.</br>
+    /// If `cache_name` is not valid or the directory could not be created, [`CacheDirError`]
+    /// is returned.<br/>
+    /// Calling `.attempts()` on it will return all the `CacheDirConfig` attempts that failed,
+    /// in the order in which they were made.<br/>
     /// Calling `.kind()` on it will return the last `std::io::ErrorKind`.<br/>
-    /// Calling `.description()` will return the description of all the `CacheDirConfig`
-    /// attempts that failed.
+    /// `CacheDirError` can be converted into an `std::io::Error`(ex: by using `?`).
     ///
     /// # Examples
     /// ```no_run
     /// use cachedir::CacheDirConfig;
-    /// let cache_dir = CacheDirConfig::new("some/path")
+    /// let cache_dir = CacheDirConfig::new("some_cache")
     ///                                .get_cache_dir();
     /// ```
-    pub fn get_cache_dir(&self) -> io::Result<CacheDir> {
-        match sys_cache::create_cache_dir(&self) {
-            Ok(path_buf) => Ok( CacheDir { path: path_buf } ),
-            Err(err)     => Err(err)
-        }
+    ///
+    /// [`CacheDirError`]: struct.CacheDirError.html
+    pub fn get_cache_dir(&self) -> Result<CacheDir, CacheDirError> {
+        sys_cache::create_cache_dir(self)
     }
 }
@@ -0,0 +1,249 @@
+use std::fs;
+use std::io;
+use std::path;
+
+// The lock file of the whole cache directory
+pub const DIR_LOCK_NAME: &str = ".cachedir.lock";
+
+/// The kind of lock to acquire on a key-value entry.
+///
+/// Used by [`CacheDir::lock_entry`] and [`CacheDir::try_lock_entry`].
+///
+/// [`CacheDir::lock_entry`]: struct.CacheDir.html#method.lock_entry
+/// [`CacheDir::try_lock_entry`]: struct.CacheDir.html#method.try_lock_entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockMode {
+    /// Any number of processes can hold a shared lock at the same time(ex: for reading).
+    Shared,
+    /// Only one process can hold an exclusive lock, while nobody holds a shared lock
+    /// (ex: for writing or for cleaning up).
+    Exclusive
+}
+
+/// A lock on a lock file from the cache directory, which is released when it is dropped.
+///
+/// Returned by [`CacheDir::lock_exclusive`], [`CacheDir::lock_shared`],
+/// [`CacheDir::lock_entry`] and their `try_` variants.
+///
+/// The locks are advisory(they only coordinate the processes that use them) and they are held
+/// by the open lock file: `flock` on Unix and `LockFileEx` on Windows. The lock is released
+/// by the OS even if the process crashes.
+///
+/// [`CacheDir::lock_exclusive`]: struct.CacheDir.html#method.lock_exclusive
+/// [`CacheDir::lock_shared`]: struct.CacheDir.html#method.lock_shared
+/// [`CacheDir::lock_entry`]: struct.CacheDir.html#method.lock_entry
+#[derive(Debug)]
+pub struct CacheLock {
+    // Closing the file releases the lock
+    _file: fs::File,
+    path:  path::PathBuf,
+    mode:  LockMode
+}
+
+impl CacheLock {
+    /// The path of the lock file.
+    pub fn path(&self) -> &path::Path {
+        &self.path
+    }
+
+    /// The kind of lock that is held.
+    pub fn mode(&self) -> LockMode {
+        self.mode
+    }
+
+    // Removes the lock file while the lock is still held, then releases the lock.
+    // The processes that wait for the lock on the removed file lock the new file instead
+    pub(crate) fn remove(self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result                                                => result
+        }
+    }
+}
+
+// Returns the lock file of an entry: `ab/cdef...` is locked with `ab/.cdef....lock`
+pub fn entry_lock_path(entry_path: &path::Path) -> path::PathBuf {
+    let name = entry_path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    entry_path.with_file_name(format!(".{}.lock", name))
+}
+
+// Returns the entry that is locked with `path`(`ab/cdef...` for `ab/.cdef....lock`),
+// or `None` if `path` is not named like a lock file. The entry might not exist
+pub fn locked_entry_path(path: &path::Path) -> Option<path::PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let entry_name = name.strip_prefix('.')?.strip_suffix(".lock")?;
+    if entry_name.is_empty() {
+        None
+    } else {
+        Some(path.with_file_name(entry_name))
+    }
+}
+
+// Opens(or creates) the lock file and locks it, waiting until the lock is released by the others
+pub fn lock(path: path::PathBuf, mode: LockMode) -> io::Result<CacheLock> {
+    loop {
+        let file = open(&path, true)?;
+        if !lock_file(&file, mode, true)? {
+            return Err(io::Error::new(io::ErrorKind::WouldBlock, "The lock file is locked"));
+        }
+        // The lock file was removed while waiting for the lock
+        if is_current(&file, &path)? {
+            return Ok(CacheLock { _file: file, path, mode });
+        }
+    }
+}
+
+// Opens(or creates) the lock file and locks it. Returns `None` if the lock is held by the others
+pub fn try_lock(path: path::PathBuf, mode: LockMode) -> io::Result<Option<CacheLock>> {
+    loop {
+        let file = open(&path, true)?;
+        if !lock_file(&file, mode, false)? {
+            return Ok(None);
+        }
+        if is_current(&file, &path)? {
+            return Ok(Some(CacheLock { _file: file, path, mode }));
+        }
+    }
+}
+
+// Locks an existing lock file exclusively, without waiting, in order to remove it.
+// Returns `None` if the lock is held by the others and a `NotFound` error if there is
+// no lock file
+pub fn try_lock_existing(path: path::PathBuf) -> io::Result<Option<CacheLock>> {
+    let file = open(&path, false)?;
+    if !lock_file(&file, LockMode::Exclusive, false)? {
+        return Ok(None);
+    }
+    if is_current(&file, &path)? {
+        Ok(Some(CacheLock { _file: file, path, mode: LockMode::Exclusive }))
+    } else {
+        Err(io::Error::new(io::ErrorKind::NotFound, "The lock file was removed"))
+    }
+}
+
+fn open(path: &path::Path, create: bool) -> io::Result<fs::File> {
+    if create {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    fs::OpenOptions::new()
+       .read(true)
+       .write(true)
+       .create(create)
+       .truncate(false)
+       .open(path)
+}
+
+// Checks that the locked file is still the lock file at `path`, because the lock files
+// of the entries are removed(while locked) together with the entries
+#[cfg(unix)]
+fn is_current(file: &fs::File, path: &path::Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let locked = file.metadata()?;
+    match fs::metadata(path) {
+        Ok(current)                                           => {
+            Ok(current.dev() == locked.dev() && current.ino() == locked.ino())
+        },
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err)                                              => Err(err)
+    }
+}
+
+#[cfg(windows)]
+fn is_current(file: &fs::File, path: &path::Path) -> io::Result<bool> {
+    use std::mem;
+    use std::os::windows::io::AsRawHandle;
+    use winapi::um::fileapi::{ BY_HANDLE_FILE_INFORMATION, GetFileInformationByHandle };
+
+    // The volume and the index of the file identify it, like the device and the inode on Unix
+    let file_id = |file: &fs::File| -> io::Result<(u32, u32, u32)> {
+        let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { mem::zeroed() };
+        if unsafe { GetFileInformationByHandle(file.as_raw_handle() as _, &mut info) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok((info.dwVolumeSerialNumber, info.nFileIndexHigh, info.nFileIndexLow))
+    };
+
+    // A file that is being removed can not be opened(`PermissionDenied`)
+    match fs::File::open(path) {
+        Ok(current)                                                   => {
+            Ok(file_id(&current)? == file_id(file)?)
+        },
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound
+                        || err.kind() == io::ErrorKind::PermissionDenied => Ok(false),
+        Err(err)                                                      => Err(err)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn is_current(_: &fs::File, _: &path::Path) -> io::Result<bool> {
+    Ok(true)
+}
+
+#[cfg(unix)]
+fn lock_file(file: &fs::File, mode: LockMode, wait: bool) -> io::Result<bool> {
+    use std::os::unix::io::AsRawFd;
+    use libc;
+
+    let mut operation = match mode {
+        LockMode::Shared    => libc::LOCK_SH,
+        LockMode::Exclusive => libc::LOCK_EX
+    };
+    if !wait {
+        operation |= libc::LOCK_NB;
+    }
+
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+            return Ok(true);
+        }
+
+        let err = io::Error::last_os_error();
+        match err.kind() {
+            io::ErrorKind::Interrupted => continue,
+            io::ErrorKind::WouldBlock  => return Ok(false),
+            _                          => return Err(err)
+        }
+    }
+}
+
+#[cfg(windows)]
+fn lock_file(file: &fs::File, mode: LockMode, wait: bool) -> io::Result<bool> {
+    use std::mem;
+    use std::os::windows::io::AsRawHandle;
+    use winapi::shared::winerror::ERROR_LOCK_VIOLATION;
+    use winapi::um::fileapi::LockFileEx;
+    use winapi::um::minwinbase::{ LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED };
+
+    let mut flags = match mode {
+        LockMode::Shared    => 0,
+        LockMode::Exclusive => LOCKFILE_EXCLUSIVE_LOCK
+    };
+    if !wait {
+        flags |= LOCKFILE_FAIL_IMMEDIATELY;
+    }
+
+    // Locks the whole file
+    let mut overlapped: OVERLAPPED = unsafe { mem::zeroed() };
+    let result = unsafe {
+        LockFileEx(file.as_raw_handle() as _, flags, 0, !0, !0, &mut overlapped)
+    };
+    if result != 0 {
+        return Ok(true);
+    }
+
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lock_file(_: &fs::File, _: LockMode, _: bool) -> io::Result<bool> {
+    Err(io::Error::new(io::ErrorKind::Other, "Locking is not supported on this OS"))
+}
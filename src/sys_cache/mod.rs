@@ -1,155 +1,668 @@
 use std::io;
 use std::path;
 use std::fs;
+use std::cmp;
+use std::ffi;
 
-pub fn create_cache_dir(cache_config: &super::CacheDirConfig)
-    -> io::Result<path::PathBuf>
-{
-    use std::error::Error;
+use cache_name::{ self, CacheNameError };
+use cachedir_tag;
+use candidate::{ CacheLocation, Candidate, CandidateStatus };
+use environment::Environment;
+use error::{ Attempt, CacheDirError };
+use fs_info::{ self, FsInfo, FsType };
+use kind::CacheKind;
+use target_os::TargetOs;
+use CacheDir;
+
+use self::redox_cache::RedoxCache;
+use self::unix_cache::UnixCache;
+use self::unknown_os_cache::UnknownOsCache;
+use self::windows_cache::WindowsCache;
+
+// The order in which the cache options are attempted, unless `CacheDirConfig::order` is used
+const DEFAULT_ORDER: [CacheKind; 5] = [CacheKind::App,
+                                       CacheKind::User,
+                                       CacheKind::System,
+                                       CacheKind::Tmp,
+                                       CacheKind::Memory];
+
+// Returns the enabled cache options in the order in which they should be attempted:
+// first the ones passed to `CacheDirConfig::order`, followed by the rest in the default order
+pub fn cache_kinds(cache_config: &super::CacheDirConfig) -> Vec<CacheKind> {
     let default_config = !cache_config.app_cache
                          && !cache_config.user_cache
                          && !cache_config.sys_cache
                          && !cache_config.tmp_cache
                          && !cache_config.mem_cache;
 
-    let user_cache = if default_config { true } else { cache_config.user_cache };
+    let is_enabled = |kind: &CacheKind| match *kind {
+        CacheKind::App    => {
+            cache_config.app_cache
+            && (cache_config.app_cache_path.is_some() || cache_config.app_default)
+        },
+        CacheKind::User   => default_config || cache_config.user_cache,
+        CacheKind::System => cache_config.sys_cache,
+        CacheKind::Tmp    => cache_config.tmp_cache,
+        CacheKind::Memory => cache_config.mem_cache
+    };
 
-    let mut last_io_error = io::ErrorKind::NotFound;
-    let mut errors_buffer = String::new();
+    let mut kinds: Vec<CacheKind> = Vec::with_capacity(DEFAULT_ORDER.len());
+    for kind in cache_config.order.iter().chain(DEFAULT_ORDER.iter()) {
+        if is_enabled(kind) && !kinds.contains(kind) {
+            kinds.push(*kind);
+        }
+    }
+    kinds
+}
+
+pub fn create_cache_dir(cache_config: &super::CacheDirConfig) -> Result<CacheDir, CacheDirError> {
+    cache_name::validate(&cache_config.cache_name, cache_config.nested_names)?;
 
-    if cache_config.app_cache && cache_config.app_cache_path.is_some() {
-        match CacheDirImpl::create_app_cache_dir(&cache_config.cache_name,
-                                                 &cache_config.app_cache_path.unwrap()) {
-            Ok(result) => return Ok(result),
-            Err(err)   => {
-                last_io_error = err.kind();
-                errors_buffer.push_str(err.description());
+    // All the failed attempts, returned only if none of the cache options succeed
+    let mut attempts: Vec<Attempt> = Vec::new();
+
+    for kind in cache_kinds(cache_config) {
+        for root in cache_roots(cache_config, kind) {
+            let root = match root {
+                Ok(root)     => root,
+                Err(attempt) => {
+                    attempts.push(attempt);
+                    continue;
+                }
+            };
+
+            let result = create_dir_helper(kind, &root, &cache_config.cache_name)
+                                 .and_then(|path| {
+                                     if cache_config.probe_writable {
+                                         if let Err(err) = probe_writable(&path) {
+                                             return Err(Attempt::new(kind, Some(path), err));
+                                         }
+                                     }
+                                     if cache_config.cachedir_tag {
+                                         if let Err(err) = cachedir_tag::tag(&path) {
+                                             return Err(Attempt::new(kind, Some(path), err));
+                                         }
+                                     }
+                                     Ok(path)
+                                 });
+
+            match result {
+                Ok(result)   => {
+                    return Ok(CacheDir { path:         result,
+                                         kind,
+                                         user_segment: root.user_segment,
+                                         fs_info:      root.fs_info,
+                                         size_limit:   cache_config.size_limit });
+                },
+                Err(attempt) => attempts.push(attempt)
             }
         }
     }
 
-    if user_cache {
-        match CacheDirImpl::create_user_cache_dir(&cache_config.cache_name) {
-            Ok(result) => return Ok(result),
-            Err(err)   => {
-                last_io_error = err.kind();
-                errors_buffer.push_str(err.description());
+    Err(CacheDirError::new(attempts))
+}
+
+// Computes the candidate cache directories without touching the filesystem,
+// other than for checking their current state
+pub fn candidates(cache_config: &super::CacheDirConfig) -> Result<Vec<Candidate>, CacheNameError> {
+    cache_name::validate(&cache_config.cache_name, cache_config.nested_names)?;
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+    for kind in cache_kinds(cache_config) {
+        for root in target_cache_roots(cache_config, TargetOs::current(), kind) {
+            let root = match root {
+                Ok(root)     => root,
+                Err(attempt) => {
+                    let path = attempt.path().map(path::Path::to_path_buf).unwrap_or_default();
+                    candidates.push(Candidate::rejected(kind, path, rejected_status(&attempt),
+                                                        attempt.error()));
+                    continue;
+                }
+            };
+
+            let path = root.path.join(root.relative_path(&cache_config.cache_name));
+            match check_file_system(cache_config, kind, root) {
+                Ok(root)               => {
+                    let status = candidate_status(&root, &path);
+                    candidates.push(Candidate::new(kind, path, status));
+                },
+                Err((status, attempt)) => {
+                    candidates.push(Candidate::rejected(kind, path, status, attempt.error()));
+                }
             }
         }
     }
+    Ok(candidates)
+}
 
-    if cache_config.sys_cache {
-        match CacheDirImpl::create_system_cache_dir(&cache_config.cache_name) {
-            Ok(result) => return Ok(result),
-            Err(err)   => {
-                last_io_error = err.kind();
-                errors_buffer.push_str(err.description());
-            }
+// Computes the cache directories that would be attempted on `target_os`,
+// without touching the filesystem at all
+pub fn locations(cache_config: &super::CacheDirConfig,
+                 target_os:    TargetOs) -> Result<Vec<CacheLocation>, CacheNameError> {
+    // The name is joined with the path rules of `target_os`, so it is validated with them too
+    cache_name::validate_for(target_os, &cache_config.cache_name, cache_config.nested_names)?;
+
+    let mut locations: Vec<CacheLocation> = Vec::new();
+    for kind in cache_kinds(cache_config) {
+        for root in target_cache_roots(cache_config, target_os, kind) {
+            let root = match root {
+                Ok(root)     => root,
+                Err(attempt) => {
+                    let path = attempt.path().map(path::Path::to_path_buf).unwrap_or_default();
+                    locations.push(CacheLocation::rejected(kind, path, rejected_status(&attempt),
+                                                           attempt.error()));
+                    continue;
+                }
+            };
+
+            let parent = match root.user_segment {
+                Some(ref segment) => join(target_os, &root.path, path::Path::new(segment)),
+                None              => root.path.clone()
+            };
+            locations.push(CacheLocation::new(kind, join(target_os, &parent,
+                                                         &cache_config.cache_name)));
         }
     }
+    Ok(locations)
+}
 
-    if cache_config.tmp_cache {
-        match CacheDirImpl::create_tmp_cache_dir(&cache_config.cache_name) {
-            Ok(result) => return Ok(result),
-            Err(err)   => {
-                last_io_error = err.kind();
-                errors_buffer.push_str(err.description());
-            }
+// ===== Private =====
+mod unix_cache;
+mod windows_cache;
+mod redox_cache;
+mod unknown_os_cache;
+
+// A parent directory inside of which the cache directory can be created
+struct CacheRoot {
+    path:   path::PathBuf,
+    // `true` if the parent directory should be created when it is missing
+    create: bool,
+    // `true` if other users can create files in the parent directory(ex: `/var/tmp`),
+    // in which case the cache directory is created with restricted permissions
+    shared: bool,
+    // Inserted between the parent directory and the cache name, when the cache is per-user
+    user_segment: Option<ffi::OsString>,
+    // `true` if the parent directory must be stored in RAM(a tmpfs or ramfs file system),
+    // for directories that are not guaranteed to be(ex: `$XDG_RUNTIME_DIR`)
+    ram_backed:   bool,
+    // The file system of the parent directory, when it was checked
+    fs_info:      Option<FsInfo>
+}
+
+impl CacheRoot {
+    fn new(path: path::PathBuf, create: bool) -> CacheRoot {
+        CacheRoot { path, create, shared: false, user_segment: None, ram_backed: false,
+                    fs_info: None }
+    }
+
+    fn shared(path: path::PathBuf, create: bool) -> CacheRoot {
+        CacheRoot { path, create, shared: true, user_segment: None, ram_backed: false,
+                    fs_info: None }
+    }
+
+    fn ram_backed(self) -> CacheRoot {
+        CacheRoot { ram_backed: true, ..self }
+    }
+
+    // The path of the cache directory, relative to the parent directory
+    fn relative_path(&self, cache_name: &path::Path) -> path::PathBuf {
+        match self.user_segment {
+            Some(ref user_segment) => path::Path::new(user_segment).join(cache_name),
+            None                   => cache_name.to_path_buf()
+        }
+    }
+}
+
+// The candidate parent directories for one kind of cache, in the order in which they should be
+// attempted. A location that could not be determined is returned as a failed attempt
+type CacheRoots = Vec<Result<CacheRoot, Attempt>>;
+
+// The functions that should be implemented by all os-specific modules.
+// They only compute the paths - they should never touch the filesystem
+trait CacheDirOperations {
+    fn app_cache_roots(&self, env: &dyn Environment, app_cache_dir: &path::Path) -> CacheRoots;
+    fn user_cache_roots(&self, env: &dyn Environment)                            -> CacheRoots;
+    fn system_cache_roots(&self, env: &dyn Environment)                          -> CacheRoots;
+    fn tmp_cache_roots(&self, env: &dyn Environment)                             -> CacheRoots;
+    fn memory_cache_roots(&self, env: &dyn Environment)                          -> CacheRoots;
+    // A name that identifies the current user(ex: the uid on Unix)
+    fn user_segment(&self, env: &dyn Environment)                      -> Option<ffi::OsString>;
+}
+
+// All the os-specific modules are compiled on every OS and selected at runtime,
+// so the paths of any OS can be computed on any other OS
+fn cache_dir_impl(target_os: TargetOs) -> &'static dyn CacheDirOperations {
+    match target_os {
+        TargetOs::Linux      => &UnixCache(TargetOs::Linux),
+        TargetOs::MacOs      => &UnixCache(TargetOs::MacOs),
+        TargetOs::Emscripten => &UnixCache(TargetOs::Emscripten),
+        TargetOs::OtherUnix  => &UnixCache(TargetOs::OtherUnix),
+        TargetOs::Windows    => &WindowsCache,
+        TargetOs::Redox      => &RedoxCache,
+        TargetOs::Unknown    => &UnknownOsCache
+    }
+}
+
+// Computes the parent directories of `kind` for `target_os`, including the per-user segments,
+// without touching the filesystem
+fn target_cache_roots(cache_config: &super::CacheDirConfig,
+                      target_os:    TargetOs,
+                      kind:         CacheKind) -> CacheRoots {
+    let cache_dir_impl = cache_dir_impl(target_os);
+    let env = &*cache_config.environment;
+    let roots = match kind {
+        CacheKind::App    => match cache_config.app_cache_path.as_ref() {
+            Some(app_cache_path)             => {
+                cache_dir_impl.app_cache_roots(env, app_cache_path)
+            },
+            None if cache_config.app_default => {
+                cache_dir_impl.app_cache_roots(env, default_app_cache_dir(target_os))
+            },
+            None                             => Vec::new()
+        },
+        CacheKind::User   => cache_dir_impl.user_cache_roots(env),
+        CacheKind::System => cache_dir_impl.system_cache_roots(env),
+        CacheKind::Tmp    => cache_dir_impl.tmp_cache_roots(env),
+        CacheKind::Memory => cache_dir_impl.memory_cache_roots(env)
+    };
+
+    if !cache_config.per_user.contains(&kind) {
+        return roots;
+    }
+
+    // System-wide caches are shared by definition, while the tmp and memory caches only
+    // need the per-user segment when their parent directory is shared between the users
+    let user_segment = cache_dir_impl.user_segment(env);
+    roots.into_iter()
+         .map(|root| root.and_then(|mut root| {
+             if kind != CacheKind::System && !root.shared {
+                 return Ok(root);
+             }
+
+             match user_segment {
+                 Some(ref user_segment) => {
+                     root.user_segment = Some(user_segment.clone());
+                     Ok(root)
+                 },
+                 None                   => {
+                     Err(Attempt::new(kind, Some(root.path),
+                                      io::Error::new(io::ErrorKind::NotFound,
+                                                     "Could not identify the current user for \
+                                                     the per-user cache directory")))
+                 }
+             }
+         }))
+         .collect()
+}
+
+// The application cache directory, relative to the current directory, when `app_cache_path`
+// is not set: the hidden `.cache` on the Unix-like systems and `Cache` on Windows
+fn default_app_cache_dir(target_os: TargetOs) -> &'static path::Path {
+    match target_os {
+        TargetOs::Windows => path::Path::new("Cache"),
+        _                 => path::Path::new(".cache")
+    }
+}
+
+// The parent directories of `kind` on the current OS, that passed the file system checks
+fn cache_roots(cache_config: &super::CacheDirConfig, kind: CacheKind) -> CacheRoots {
+    target_cache_roots(cache_config, TargetOs::current(), kind)
+        .into_iter()
+        .map(|root| root.and_then(|root| {
+            check_file_system(cache_config, kind, root).map_err(|(_, attempt)| attempt)
+        }))
+        .collect()
+}
+
+// The status of a location that was rejected before its file system could be checked:
+// the backends reject the paths that are not valid(ex: a relative `$XDG_CACHE_HOME`)
+// with `InvalidInput`, any other failure means that the location could not be determined
+fn rejected_status(attempt: &Attempt) -> CandidateStatus {
+    if attempt.error().kind() == io::ErrorKind::InvalidInput {
+        CandidateStatus::Invalid
+    } else {
+        CandidateStatus::Unavailable
+    }
+}
+
+// Joins `path` onto `base` with the path separator of `target_os`, so the paths computed
+// for another OS(ex: Windows paths computed on Linux) are valid on that OS
+fn join(target_os: TargetOs, base: &path::Path, path: &path::Path) -> path::PathBuf {
+    if target_os.separator() == path::MAIN_SEPARATOR {
+        return base.join(path);
+    }
+    if is_absolute(target_os, path) {
+        return path.to_path_buf();
+    }
+
+    // The path is split with the separators of `target_os`, because the current OS
+    // doesn't know them(ex: `org\app` is a single component on Linux)
+    let is_separator = |c: char| c == '/' || (target_os == TargetOs::Windows && c == '\\');
+    let mut joined = base.as_os_str().to_os_string();
+    let path = path.to_string_lossy();
+    for component in path.split(is_separator).filter(|c| !c.is_empty() && *c != ".") {
+        if !joined.is_empty() && !joined.to_string_lossy().ends_with(is_separator) {
+            joined.push(target_os.separator().to_string());
+        }
+        joined.push(component);
+    }
+    path::PathBuf::from(joined)
+}
+
+// Checks if `path` is absolute on `target_os`, which can be another OS than the current one
+fn is_absolute(target_os: TargetOs, path: &path::Path) -> bool {
+    if target_os.separator() == path::MAIN_SEPARATOR {
+        return path.is_absolute();
+    }
+
+    // Only the ASCII prefix is checked, which is kept by the lossy conversion
+    match (target_os, path.to_string_lossy().as_bytes()) {
+        // `C:\path`, `C:/path` or a UNC path(`\\server\share`)
+        (TargetOs::Windows, &[drive, b':', separator, ..]) => {
+            drive.is_ascii_alphabetic() && (separator == b'\\' || separator == b'/')
+        },
+        (TargetOs::Windows, bytes)                        => bytes.starts_with(b"\\\\"),
+        (_, bytes)                                        => bytes.starts_with(b"/")
+    }
+}
+
+// Finds the home directory of the current user on `target_os`:
+// 1. Unix and Redox: `$HOME`, then the home directory from the passwd entry of the user
+// 2. Windows: the `Profile` known folder, then `%USERPROFILE%`(`%HOME%` is never used)
+// Empty paths are ignored. The error describes every source that was tried
+fn home_dir(target_os: TargetOs, env: &dyn Environment) -> Result<path::PathBuf, String> {
+    // The name of the variable, and how it is written in the error messages
+    let (var_name, var_display, os_source) = if target_os == TargetOs::Windows {
+        ("USERPROFILE", "%USERPROFILE%", "the Profile known folder is not available")
+    } else {
+        ("HOME", "$HOME", "the user has no home directory in the passwd database")
+    };
+    let var = env.var_os(var_name);
+
+    let from_var = var.as_ref().filter(|path| !path.is_empty()).map(path::PathBuf::from);
+    let from_os = || env.home_dir().filter(|path| !path.as_os_str().is_empty());
+    let home_dir = if target_os == TargetOs::Windows {
+        from_os().or(from_var)
+    } else {
+        from_var.or_else(from_os)
+    };
+
+    home_dir.ok_or_else(|| {
+        let var_status = match var {
+            Some(_) => format!("{} is set to an empty string", var_display),
+            None    => format!("{} is not set", var_display)
+        };
+        if target_os == TargetOs::Windows {
+            format!("Could not obtain user's home directory: {} and {}", os_source, var_status)
+        } else {
+            format!("Could not obtain user's home directory: {} and {}", var_status, os_source)
+        }
+    })
+}
+
+// The locations that don't have the required free space are skipped, as well as
+// the memory caches that are not stored in RAM(when required). The error includes the status
+// of the rejected location, for `candidates`
+fn check_file_system(cache_config: &super::CacheDirConfig,
+                     kind:         CacheKind,
+                     mut root:     CacheRoot) -> Result<CacheRoot, (CandidateStatus, Attempt)> {
+    let (ram_only, min_free_space) = if kind == CacheKind::Memory {
+        (root.ram_backed || cache_config.mem_ram_only,
+         cmp::max(cache_config.mem_free_space, cache_config.min_free_space))
+    } else {
+        (false, cache_config.min_free_space)
+    };
+
+    if kind != CacheKind::Memory && min_free_space == 0 {
+        return Ok(root);
+    }
+
+    // A parent directory that will be created does not exist yet, so we check the closest
+    // existing ancestor instead, which is usually on the same file system
+    let fs_path = if root.create {
+        root.path.ancestors().find(|ancestor| ancestor.exists()).unwrap_or(&root.path)
+    } else {
+        &root.path
+    };
+
+    let fs_info = match fs_info::query(fs_path) {
+        Ok(fs_info) => fs_info,
+        // The directory might be missing, which is reported when attempting to create the cache
+        Err(_) if !ram_only && min_free_space == 0 => return Ok(root),
+        Err(err)                                   => {
+            let status = if ram_only {
+                CandidateStatus::NotRamBacked
+            } else {
+                CandidateStatus::InsufficientSpace
+            };
+            return Err((status, Attempt::new(kind, Some(root.path), err)));
+        }
+    };
+
+    // Outside of Linux and Android, a RAM-backed file system can't be told apart from a disk
+    if ram_only && fs_info.fs_type() == FsType::Unknown {
+        let err = io::Error::new(io::ErrorKind::Other,
+                                 "The type of the file system can not be determined on this OS, \
+                                  so the directory can not be verified to be stored in RAM");
+        return Err((CandidateStatus::Unverifiable, Attempt::new(kind, Some(root.path), err)));
+    }
+
+    if ram_only && !fs_info.is_ram_backed() {
+        let err = io::Error::new(io::ErrorKind::Other,
+                                 format!("The directory is not stored on a tmpfs or ramfs({})",
+                                         fs_info.fs_type()));
+        return Err((CandidateStatus::NotRamBacked, Attempt::new(kind, Some(root.path), err)));
+    }
+
+    if fs_info.free_space() < min_free_space {
+        let err = io::Error::new(io::ErrorKind::Other,
+                                 format!("Only {} bytes are free, but {} bytes are required",
+                                         fs_info.free_space(), min_free_space));
+        return Err((CandidateStatus::InsufficientSpace, Attempt::new(kind, Some(root.path), err)));
+    }
+
+    root.fs_info = Some(fs_info);
+    Ok(root)
+}
+
+// Shortcut for a failed attempt that could not even determine the path to try
+fn unavailable(kind: CacheKind, reason: &str) -> Result<CacheRoot, Attempt> {
+    Err(Attempt::new(kind, None, io::Error::new(io::ErrorKind::NotFound, reason)))
+}
+
+// Creates the cache directory inside of the parent cache directory
+fn create_dir_helper(kind: CacheKind,
+                     root: &CacheRoot,
+                     path: &path::Path) -> Result<path::PathBuf, Attempt> {
+    let parent_cache_dir = &root.path;
+    let path = &root.relative_path(path);
+    if root.create {
+        if let Err(err) = fs::create_dir_all(parent_cache_dir) {
+            return Err(Attempt::new(kind, Some(parent_cache_dir.clone()), err));
         }
     }
 
-    if cache_config.mem_cache {
-        match CacheDirImpl::create_memory_cache_dir(&cache_config.cache_name) {
-            Ok(result) => return Ok(result),
-            Err(err)   => {
-                last_io_error = err.kind();
-                errors_buffer.push_str(err.description());
+    if !parent_cache_dir.exists() {
+        Err(Attempt::new(kind,
+                         Some(parent_cache_dir.clone()),
+                         io::Error::new(io::ErrorKind::NotFound,
+                                        "Parent cache directory does not exist")))
+    } else if !parent_cache_dir.is_dir() {
+        Err(Attempt::new(kind,
+                         Some(parent_cache_dir.clone()),
+                         io::Error::new(io::ErrorKind::AlreadyExists,
+                                        "Parent cache path is not a directory")))
+    } else if root.shared {
+        create_private_dir(parent_cache_dir, path).map_err(|(path, err)| {
+            Attempt::new(kind, Some(path), err)
+        })
+    } else {
+        let final_cache_path = parent_cache_dir.join(path);
+        match fs::create_dir_all(&final_cache_path) {
+            Ok(_)    => Ok(final_cache_path),
+            Err(err) => Err(Attempt::new(kind, Some(final_cache_path), err))
+        }
+    }
+}
+
+// Creates and removes a uniquely named file inside the cache directory, because an existing
+// directory can still be read-only(ex: on a read-only mount or owned by another user)
+fn probe_writable(cache_dir: &path::Path) -> io::Result<()> {
+    use std::process;
+    use std::sync::atomic::{ AtomicUsize, Ordering };
+
+    // Makes the name unique between the threads of the same process
+    static PROBE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let probe_path = cache_dir.join(format!(".cachedir-probe.{}.{}.tmp", process::id(),
+                                            PROBE_COUNTER.fetch_add(1, Ordering::Relaxed)));
+    let result = fs::OpenOptions::new()
+                     .write(true)
+                     .create_new(true)
+                     .open(&probe_path)
+                     .and_then(|_| fs::remove_file(&probe_path));
+
+    result.map_err(|err| {
+        io::Error::new(err.kind(), format!("The cache directory is not writable: {}", err))
+    })
+}
+
+// Creates the cache directory inside of a parent directory that is shared with other users.
+// Every missing directory is created with the `0700` mode. The existing directories are reused
+// only if they are not symbolic links and if they are owned by the current user, otherwise
+// another user could have prepared them in advance to read or tamper with the cache.
+// On failure, it returns the path that was rejected together with the reason
+#[cfg(unix)]
+fn create_private_dir(parent_cache_dir: &path::Path,
+                      path: &path::Path) -> Result<path::PathBuf, (path::PathBuf, io::Error)> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    let mut final_cache_path = parent_cache_dir.to_path_buf();
+    for component in normal_components(path) {
+        final_cache_path.push(component);
+
+        match fs::DirBuilder::new().mode(0o700).create(&final_cache_path) {
+            Ok(_)                                                      => continue,
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {},
+            Err(err)                                                   => {
+                return Err((final_cache_path, err));
             }
         }
+
+        // The directory already existed(or was created by another process in the meantime)
+        if let Err(err) = check_private_dir(&final_cache_path) {
+            return Err((final_cache_path, err));
+        }
     }
 
-    Err(io::Error::new(last_io_error, errors_buffer))
+    Ok(final_cache_path)
 }
 
-// ===== Private =====
+#[cfg(not(unix))]
+fn create_private_dir(parent_cache_dir: &path::Path,
+                      path: &path::Path) -> Result<path::PathBuf, (path::PathBuf, io::Error)> {
+    let final_cache_path = parent_cache_dir.join(path);
+    match fs::create_dir_all(&final_cache_path) {
+        Ok(_)    => Ok(final_cache_path),
+        Err(err) => Err((final_cache_path, err))
+    }
+}
+
+// The components of a validated cache name, without the `.` components
+fn normal_components(path: &path::Path) -> Vec<&ffi::OsStr> {
+    path.components()
+        .filter_map(|component| match component {
+            path::Component::Normal(component) => Some(component),
+            _                                  => None
+        })
+        .collect()
+}
+
+// Checks that an existing directory is not a symbolic link and is owned by the current user
 #[cfg(unix)]
-#[path = "unix_cache.rs"]
-mod cache_impl;
+fn check_private_dir(path: &path::Path) -> io::Result<()> {
+    use std::os::unix::fs::MetadataExt;
+    use libc;
 
-#[cfg(windows)]
-#[path = "windows_cache.rs"]
-mod cache_impl;
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.file_type().is_symlink() {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                  "The cache directory is a symbolic link \
+                                  inside of a shared directory"));
+    }
 
-#[cfg(target_os = "redox")]
-#[path = "redox_cache.rs"]
-mod cache_impl;
+    if !metadata.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                                  "The cache path is not a directory"));
+    }
 
-#[cfg(not(any(unix, windows, target_os = "redox")))]
-#[path = "unknown_os_cache.rs"]
-mod cache_impl;
+    let uid = unsafe { libc::geteuid() };
+    if metadata.uid() != uid {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied,
+                                  format!("The cache directory is owned by another user \
+                                          (uid: {}) inside of a shared directory", metadata.uid())));
+    }
 
-// All os-specific modules implement `CacheDirOperations` on the `CacheDirImpl` structure
-struct CacheDirImpl;
+    Ok(())
+}
 
-// The functions that should be implemented by all os-specific modules
-trait CacheDirOperations {
-    fn create_app_cache_dir(cache_name:    &path::Path,
-                            app_cache_dir: &path::Path) -> io::Result<path::PathBuf>;
-    fn create_user_cache_dir(cache_name:   &path::Path) -> io::Result<path::PathBuf>;
-    fn create_system_cache_dir(cache_name: &path::Path) -> io::Result<path::PathBuf>;
-    fn create_tmp_cache_dir(cache_name:    &path::Path) -> io::Result<path::PathBuf>;
-    fn create_memory_cache_dir(cache_name: &path::Path) -> io::Result<path::PathBuf>;
-}
-
-// Common function shared between all implementations of the CacheDirOperations trait
-fn create_dir_helper(dirs: &[path::PathBuf], path: &path::Path) -> io::Result<path::PathBuf> {
-    // Sadly, we don't have something like `static_assert`
-    debug_assert!(!dirs.is_empty(),
-                  "Code-logic error: the slice of directories should not be empty");
-    if dirs.is_empty() {
-        return Err(io::Error::new(io::ErrorKind::NotFound,
-                                  "Could not create the cache directory"));
-    }
-
-    // This is a buffer of all the errors(attempts) to create a cache directory.
-    // The `PermissionDenied` error will be returned with this buffer only if all the attempts fail
-    let mut attempted_paths_error = String::new();
-    let mut last_io_error = io::ErrorKind::NotFound;
-    for parent_cache_dir in dirs {
-        if !parent_cache_dir.exists() {
-            last_io_error = io::ErrorKind::NotFound;
-            attempted_paths_error.push_str(
-                &format!("\n[NotFound]: Parent cache directory does not exist: {}",
-                         parent_cache_dir.display()));
-        } else {
-            if !parent_cache_dir.is_dir() {
-                last_io_error = io::ErrorKind::AlreadyExists;
-                attempted_paths_error.push_str(
-                    &format!("\n[AlreadyExists]: Parent cache path is not a directory: {}",
-                             parent_cache_dir.display())
-                );
-            } else {
-                let final_cache_path = &parent_cache_dir.join(path);
-                if let Err(err) = fs::create_dir_all(&final_cache_path) {
-                    last_io_error = err.kind();
-                    attempted_paths_error.push_str(
-                        &format!("\n[{:?}]: Failed to create the cache directory: {}",
-                                 err.kind(),
-                                 final_cache_path.display()));
-                } else {
-                    return Ok(path::PathBuf::from(final_cache_path));
-                }
+#[cfg(not(unix))]
+fn check_private_dir(_: &path::Path) -> io::Result<()> {
+    Ok(())
+}
+
+// Predicts what `create_dir_helper` would do, without creating anything
+fn candidate_status(root: &CacheRoot, path: &path::Path) -> CandidateStatus {
+    if root.shared {
+        let relative_path = path.strip_prefix(&root.path).unwrap_or(path);
+        let mut current_path = root.path.clone();
+        for component in normal_components(relative_path) {
+            current_path.push(component);
+            if fs::symlink_metadata(&current_path).is_err() {
+                break;
+            }
+            if check_private_dir(&current_path).is_err() {
+                return CandidateStatus::Insecure;
             }
         }
     }
 
-    Err(io::Error::new(last_io_error, attempted_paths_error))
+    if let Ok(metadata) = fs::metadata(path) {
+        return if !metadata.is_dir() {
+            CandidateStatus::NotADirectory
+        } else if is_writable(path) {
+            CandidateStatus::Exists
+        } else {
+            CandidateStatus::ExistsReadOnly
+        };
+    }
+
+    if !root.create && !root.path.exists() {
+        return CandidateStatus::MissingParent;
+    }
+
+    // The closest existing ancestor is where the first directory would be created
+    match path.ancestors().skip(1).find(|ancestor| ancestor.exists()) {
+        Some(ancestor) if ancestor.is_dir() && is_writable(ancestor) => CandidateStatus::Creatable,
+        Some(ancestor) if ancestor.is_dir()                          => CandidateStatus::NotWritable,
+        Some(_)                                                      => CandidateStatus::NotADirectory,
+        None                                                         => CandidateStatus::MissingParent
+    }
+}
+
+#[cfg(unix)]
+fn is_writable(path: &path::Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use libc;
+
+    match CString::new(path.as_os_str().as_bytes()) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 },
+        Err(_)   => false
+    }
 }
 
-// Making sure that the `CacheDirOperations` trait was implemented on `CacheDirImpl`
-mod testing {
-    #![allow(dead_code)]
-    use super::{ CacheDirImpl, CacheDirOperations };
-    fn is_trait_implemented<T: CacheDirOperations>(_cache_dir_impl: T) {}
-    fn assert_trait_impl() { is_trait_implemented(CacheDirImpl); }
+#[cfg(not(unix))]
+fn is_writable(path: &path::Path) -> bool {
+    fs::metadata(path).map(|metadata| !metadata.permissions().readonly()).unwrap_or(false)
 }
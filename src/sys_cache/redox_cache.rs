@@ -1,76 +1,64 @@
-use std::error::Error;
-use std::path::{ Path, PathBuf };
+use std::path::Path;
 use std::io;
-use std::fs;
-use std::env;
+use std::ffi::OsString;
 
-use super::{ CacheDirImpl, CacheDirOperations };
+use environment::Environment;
+use error::Attempt;
+use kind::CacheKind;
+use target_os::TargetOs;
 
-impl CacheDirOperations for CacheDirImpl {
-    fn create_app_cache_dir(cache_name:    &Path,
-                            app_cache_dir: &Path) -> io::Result<PathBuf> {
-        let current_dir = env::current_dir();
-        if let Err(err) = current_dir {
-            return Err(io::Error::new(err.kind(),
-                                      format!("{}\n[Application Cache]: Could not obtain the \
-                                              current directory", err.description())));
-        }
-
-        let app_cache_dir = current_dir.unwrap().join(app_cache_dir.to_path_buf());
-        if let Err(err) = fs::create_dir_all(&app_cache_dir) {
-            return Err(io::Error::new(err.kind(),
-                                      format!("{}\n[Application Cache]: Failed to create the \
-                                              parent cache directory: {}",
-                                              err.description(), app_cache_dir.display())));
-        }
-
-        super::create_dir_helper(&[app_cache_dir], &cache_name)
-    }
+use super::{ CacheDirOperations, CacheRoot, CacheRoots };
 
-    fn create_user_cache_dir(cache_name: &Path)   -> io::Result<PathBuf> {
-        let cache_dir = env::home_dir()
-                            .and_then(|path| {
-                                if path.as_os_str().is_empty() {
-                                    None
-                                } else {
-                                    Some(path.join(".cache"))
-                                }
-                            });
+// The rules of Redox
+pub struct RedoxCache;
 
-        if cache_dir.is_none() {
-            return Err(io::Error::new(io::ErrorKind::NotFound,
-                                      "[User Cache]: Could not obtain user's home directory"));
+impl CacheDirOperations for RedoxCache {
+    fn app_cache_roots(&self, env: &dyn Environment, app_cache_dir: &Path) -> CacheRoots {
+        match env.current_dir() {
+            Ok(current_dir) => {
+                vec![Ok(CacheRoot::new(super::join(TargetOs::Redox, &current_dir, app_cache_dir),
+                                       true))]
+            },
+            Err(err)        => {
+                vec![Err(Attempt::new(CacheKind::App, None,
+                                      io::Error::new(err.kind(),
+                                                     format!("Could not obtain the \
+                                                             current directory: {}", err))))]
+            }
         }
+    }
 
-        let cache_dir = cache_dir.unwrap();
-        if let Err(err) = fs::create_dir_all(&cache_dir) {
-            return Err(io::Error::new(err.kind(), format!("{}\n\
-                                                  [User Cache]: Failed to create the parent \
-                                                  cache directory", err.description())));
+    fn user_cache_roots(&self, env: &dyn Environment) -> CacheRoots {
+        match super::home_dir(TargetOs::Redox, env) {
+            Ok(home_dir) => {
+                let cache_dir = super::join(TargetOs::Redox, &home_dir, Path::new(".cache"));
+                vec![Ok(CacheRoot::new(cache_dir, true))]
+            },
+            Err(reason)  => vec![super::unavailable(CacheKind::User, &reason)]
         }
-
-        super::create_dir_helper(&[cache_dir], &cache_name)
     }
 
-    fn create_system_cache_dir(_: &Path)          -> io::Result<PathBuf> {
+    fn system_cache_roots(&self, _: &dyn Environment) -> CacheRoots {
         // I don't know where the system-wide cache folder is in `Redox`
-        Err(io::Error::new(io::ErrorKind::NotFound,
-                           "[System Cache]: System caches are not supported on Redox"))
+        vec![super::unavailable(CacheKind::System, "System caches are not supported on Redox")]
     }
 
-    fn create_tmp_cache_dir(cache_name: &Path)    -> io::Result<PathBuf> {
-        let temp_dir = env::temp_dir();
+    fn tmp_cache_roots(&self, env: &dyn Environment)  -> CacheRoots {
+        let temp_dir = env.temp_dir();
 
         if temp_dir.as_os_str().is_empty() {
-            Err(io::Error::new(io::ErrorKind::NotFound,
-                               "[Tmp Cache]: Could not obtain the temporary directory's path"))
+            vec![super::unavailable(CacheKind::Tmp,
+                                    "Could not obtain the temporary directory's path")]
         } else {
-            super::create_dir_helper(&[temp_dir], &cache_name)
+            vec![Ok(CacheRoot::new(temp_dir, false))]
         }
     }
 
-    fn create_memory_cache_dir(_: &Path)          -> io::Result<PathBuf> {
-        Err(io::Error::new(io::ErrorKind::NotFound,
-                           "[Memory Cache]: Memory caches are not supported on Redox"))
+    fn memory_cache_roots(&self, _: &dyn Environment) -> CacheRoots {
+        vec![super::unavailable(CacheKind::Memory, "Memory caches are not supported on Redox")]
+    }
+
+    fn user_segment(&self, env: &dyn Environment)     -> Option<OsString> {
+        env.var_os("USER").and_then(|name| if name.is_empty() { None } else { Some(name) })
     }
 }
@@ -1,117 +1,133 @@
-use std::error::Error;
 use std::path::{ Path, PathBuf };
 use std::io;
-use std::fs;
-use std::env;
+use std::ffi::OsString;
 
-use super::{ CacheDirImpl, CacheDirOperations };
+use environment::Environment;
+use error::Attempt;
+use kind::CacheKind;
+use target_os::TargetOs;
 
-impl CacheDirOperations for CacheDirImpl {
-    fn create_app_cache_dir(cache_name:    &Path,
-                            app_cache_dir: &Path) -> io::Result<PathBuf> {
-        let current_dir = env::current_dir();
-        if let Err(err) = current_dir {
-            return Err(io::Error::new(err.kind(),
-                                      format!("{}\n[Application Cache]: Could not obtain the \
-                                              current directory", err.description())));
-        }
+use super::{ CacheDirOperations, CacheRoot, CacheRoots };
 
-        let app_cache_dir = current_dir.unwrap().join(app_cache_dir.to_path_buf());
-        if let Err(err) = fs::create_dir_all(&app_cache_dir) {
-            return Err(io::Error::new(err.kind(),
-                                      format!("{}\n[Application Cache]: Failed to create the \
-                                              parent cache directory: {}",
-                                              err.description(), app_cache_dir.display())));
-        }
+// The rules of the Unix-like systems, which differ slightly between the `TargetOs` variants
+pub struct UnixCache(pub TargetOs);
 
-        super::create_dir_helper(&[app_cache_dir], &cache_name)
+impl CacheDirOperations for UnixCache {
+    fn app_cache_roots(&self, env: &dyn Environment, app_cache_dir: &Path) -> CacheRoots {
+        match env.current_dir() {
+            Ok(current_dir) => {
+                vec![Ok(CacheRoot::new(super::join(self.0, &current_dir, app_cache_dir), true))]
+            },
+            Err(err)        => {
+                vec![Err(Attempt::new(CacheKind::App, None,
+                                      io::Error::new(err.kind(),
+                                                     format!("Could not obtain the \
+                                                             current directory: {}", err))))]
+            }
+        }
     }
 
-    fn create_user_cache_dir(cache_name: &Path)   -> io::Result<PathBuf> {
-        // Lets see if we can get the `$HOME` path - it could be missing
-        // in a bare-bones `Linux` container or in `Emscripten` - as examples
-        let cache_dir = env::home_dir()
-                            .and_then(|path| {
-                                if path.as_os_str().is_empty() {
-                                    None
-                                } else {
-                                    if cfg!(not(target_os = "macos")) {
-                                        Some(path.join(".cache"))
-                                    } else {
-                                        Some(path.join("Library/Caches"))
-                                    }
-                                }
-                            })
-                            .or_else(|| {
-                                // On `Emscripten` we have rights to create files
-                                // and directories anywhere, so we'll still try to create
-                                // the cache directory without having `$HOME`
-                                if cfg!(target_os = "emscripten") {
-                                    Some(PathBuf::from("/var/cache"))
-                                } else {
-                                    None
-                                }
-                            });
-
-        if cache_dir.is_none() {
-            return Err(io::Error::new(io::ErrorKind::NotFound,
-                                      "[User Cache]: Could not obtain user's home directory"));
-        }
+    fn user_cache_roots(&self, env: &dyn Environment) -> CacheRoots {
+        // On non-macOS systems we follow the XDG Base Directory Specification:
+        // a non-empty `$XDG_CACHE_HOME` takes precedence over `$HOME/.cache`.
+        // The specification requires relative paths to be considered invalid
+        let xdg_cache_home = if self.0 != TargetOs::MacOs {
+            env.var_os("XDG_CACHE_HOME")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+        } else {
+            None
+        };
 
-        // Lets make sure that the parent cache directory exists
-        let cache_dir = cache_dir.unwrap();
-        if let Err(err) = fs::create_dir_all(&cache_dir) {
-            return Err(io::Error::new(err.kind(),
-                                      format!("{}\n[User Cache]: Failed to create the \
-                                              parent cache directory: {}",
-                                              err.description(), cache_dir.display())));
+        let is_relative = |path: &&PathBuf| !super::is_absolute(self.0, path);
+        if let Some(path) = xdg_cache_home.as_ref().filter(is_relative) {
+            return vec![Err(Attempt::new(CacheKind::User, Some(path.clone()),
+                                         io::Error::new(io::ErrorKind::InvalidInput,
+                                                        "$XDG_CACHE_HOME must be an \
+                                                        absolute path")))];
         }
 
-        super::create_dir_helper(&[cache_dir], &cache_name)
-    }
+        // Lets see if we can get the home directory - it could be missing
+        // in a bare-bones `Linux` container or in `Emscripten` - as examples
+        let cache_dir = match xdg_cache_home {
+            Some(path) => Ok(path),
+            None       => super::home_dir(self.0, env).map(|home_dir| {
+                if self.0 != TargetOs::MacOs {
+                    super::join(self.0, &home_dir, Path::new(".cache"))
+                } else {
+                    super::join(self.0, &home_dir, Path::new("Library/Caches"))
+                }
+            })
+        };
 
-    fn create_system_cache_dir(cache_name: &Path) -> io::Result<PathBuf> {
-        if cfg!(not(target_os = "macos")) {
-            if cfg!(target_os = "emscripten") {
-                let _ = fs::create_dir_all("/var/cache");
+        // The parent cache directory is created if it is missing.
+        // On `Emscripten` we have rights to create files and directories anywhere,
+        // so we'll still try to create the cache directory without the home directory
+        match cache_dir {
+            Ok(cache_dir)                            => vec![Ok(CacheRoot::new(cache_dir, true))],
+            Err(_) if self.0 == TargetOs::Emscripten => {
+                vec![Ok(CacheRoot::new(PathBuf::from("/var/cache"), true))]
+            },
+            Err(reason)                              => {
+                vec![super::unavailable(CacheKind::User, &reason)]
             }
+        }
+    }
 
-            super::create_dir_helper(&[PathBuf::from("/var/cache")],
-                                     &cache_name)
+    fn system_cache_roots(&self, _: &dyn Environment) -> CacheRoots {
+        if self.0 != TargetOs::MacOs {
+            vec![Ok(CacheRoot::new(PathBuf::from("/var/cache"), self.0 == TargetOs::Emscripten))]
         } else {
-            super::create_dir_helper(&[PathBuf::from("/Library/Caches")],
-                                     &cache_name)
+            vec![Ok(CacheRoot::new(PathBuf::from("/Library/Caches"), false))]
         }
     }
 
-    fn create_tmp_cache_dir(cache_name: &Path)    -> io::Result<PathBuf> {
-        let temp_dir = env::temp_dir();
-
-        // We try `/var/tmp` first because the directory is persistent between system restarts
-        if temp_dir.as_os_str().is_empty() {
-            if cfg!(target_os = "emscripten") {
-                let _ = fs::create_dir_all("/var/tmp");
-            }
+    fn tmp_cache_roots(&self, env: &dyn Environment) -> CacheRoots {
+        let temp_dir = env.temp_dir();
 
-            super::create_dir_helper(&[PathBuf::from("/var/tmp")],
-                                     &cache_name)
-        } else {
-            super::create_dir_helper(&[PathBuf::from("/var/tmp"), temp_dir],
-                                     &cache_name)
+        // We try `/var/tmp` first because the directory is persistent between system restarts.
+        // Both directories are writable by all the users
+        let mut roots = vec![Ok(CacheRoot::shared(PathBuf::from("/var/tmp"),
+                                                  self.0 == TargetOs::Emscripten))];
+        if !temp_dir.as_os_str().is_empty() {
+            roots.push(Ok(CacheRoot::shared(temp_dir, false)));
         }
+        roots
     }
 
-    fn create_memory_cache_dir(cache_name: &Path) -> io::Result<PathBuf> {
-        if cfg!(any(target_os = "linux", target_os = "emscripten")) {
-            if cfg!(target_os = "emscripten") {
-                let _ = fs::create_dir_all("/dev/shm");
+    fn memory_cache_roots(&self, env: &dyn Environment) -> CacheRoots {
+        if self.0 == TargetOs::Linux || self.0 == TargetOs::Emscripten {
+            let mut roots: CacheRoots = Vec::with_capacity(3);
+
+            // `$XDG_RUNTIME_DIR`(usually `/run/user/<uid>`) is preferred because it belongs
+            // only to the current user, but it is not required to be stored in RAM
+            if self.0 == TargetOs::Linux {
+                match env.var_os("XDG_RUNTIME_DIR").filter(|path| !path.is_empty()) {
+                    Some(ref path) if !super::is_absolute(self.0, Path::new(path)) => {
+                        roots.push(Err(Attempt::new(CacheKind::Memory, Some(PathBuf::from(path)),
+                                                    io::Error::new(io::ErrorKind::InvalidInput,
+                                                                   "$XDG_RUNTIME_DIR must be an \
+                                                                   absolute path"))));
+                    },
+                    Some(path) => roots.push(Ok(CacheRoot::new(PathBuf::from(path), false)
+                                                          .ram_backed())),
+                    None       => {}
+                }
             }
 
-            super::create_dir_helper(&[PathBuf::from("/dev/shm"), PathBuf::from("/run/shm")],
-                                     &cache_name)
+            roots.push(Ok(CacheRoot::shared(PathBuf::from("/dev/shm"),
+                                            self.0 == TargetOs::Emscripten)));
+            roots.push(Ok(CacheRoot::shared(PathBuf::from("/run/shm"), false)));
+            roots
         } else {
-            Err(io::Error::new(io::ErrorKind::NotFound,
-                               "[Memory Cache]: Memory caches are not supported on this OS"))
+            vec![super::unavailable(CacheKind::Memory,
+                                    "Memory caches are not supported on this OS")]
         }
     }
+
+    fn user_segment(&self, env: &dyn Environment) -> Option<OsString> {
+        // The effective uid is used instead of the username because it can't be spoofed
+        // by changing the environment variables and it is always available
+        env.uid().map(|uid| OsString::from(uid.to_string()))
+    }
 }
@@ -1,31 +1,36 @@
-use std::path::{ Path, PathBuf };
-use std::io;
+use std::path::Path;
+use std::ffi::OsString;
 
-use super::{ CacheDirImpl, CacheDirOperations };
+use environment::Environment;
+use kind::CacheKind;
 
-impl CacheDirOperations for CacheDirImpl {
-    fn create_app_cache_dir(_: &Path, _: &Path) -> io::Result<PathBuf> {
-        Err(io::Error::new(io::ErrorKind::NotFound,
-                           "[Application Cache]: This OS is not supported"))
+use super::{ CacheDirOperations, CacheRoots };
+
+// The operating systems without known cache locations
+pub struct UnknownOsCache;
+
+impl CacheDirOperations for UnknownOsCache {
+    fn app_cache_roots(&self, _: &dyn Environment, _: &Path) -> CacheRoots {
+        vec![super::unavailable(CacheKind::App, "This OS is not supported")]
+    }
+
+    fn user_cache_roots(&self, _: &dyn Environment)   -> CacheRoots {
+        vec![super::unavailable(CacheKind::User, "This OS is not supported")]
     }
 
-    fn create_user_cache_dir(_: &Path)   -> io::Result<PathBuf> {
-        Err(io::Error::new(io::ErrorKind::NotFound,
-                           "[User Cache]: This OS is not supported"))
+    fn system_cache_roots(&self, _: &dyn Environment) -> CacheRoots {
+        vec![super::unavailable(CacheKind::System, "This OS is not supported")]
     }
 
-    fn create_system_cache_dir(_: &Path) -> io::Result<PathBuf> {
-        Err(io::Error::new(io::ErrorKind::NotFound,
-                           "[System Cache]: This OS is not supported"))
+    fn tmp_cache_roots(&self, _: &dyn Environment)    -> CacheRoots {
+        vec![super::unavailable(CacheKind::Tmp, "This OS is not supported")]
     }
 
-    fn create_tmp_cache_dir(_: &Path)    -> io::Result<PathBuf> {
-        Err(io::Error::new(io::ErrorKind::NotFound,
-                           "[Tmp Cache]: This OS is not supported"))
+    fn memory_cache_roots(&self, _: &dyn Environment) -> CacheRoots {
+        vec![super::unavailable(CacheKind::Memory, "This OS is not supported")]
     }
 
-    fn create_memory_cache_dir(_: &Path) -> io::Result<PathBuf> {
-        Err(io::Error::new(io::ErrorKind::NotFound,
-                           "[Memory Cache]: This OS is not supported"))
+    fn user_segment(&self, _: &dyn Environment)       -> Option<OsString> {
+        None
     }
 }
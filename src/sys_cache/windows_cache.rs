@@ -1,149 +1,99 @@
-use std::error::Error;
 use std::path::{ Path, PathBuf };
 use std::io;
-use std::fs;
-use std::env;
-
-use super::{ CacheDirImpl, CacheDirOperations };
-
-impl CacheDirOperations for CacheDirImpl {
-    fn create_app_cache_dir(cache_name:    &Path,
-                            app_cache_dir: &Path) -> io::Result<PathBuf> {
-        let current_dir = env::current_dir();
-        if let Err(err) = current_dir {
-            return Err(io::Error::new(err.kind(),
-                                      format!("{}\n[Application Cache]: Could not obtain the \
-                                              current directory", err.description())));
-        }
-
-        let app_cache_dir = current_dir.unwrap().join(app_cache_dir.to_path_buf());
-        if let Err(err) = fs::create_dir_all(&app_cache_dir) {
-            return Err(io::Error::new(err.kind(),
-                                      format!("{}\n[Application Cache]: Failed to create the \
-                                              parent cache directory: {}",
-                                              err.description(), app_cache_dir.display())));
+use std::ffi::OsString;
+
+use environment::{ Environment, KnownFolder };
+use error::Attempt;
+use kind::CacheKind;
+use target_os::TargetOs;
+
+use super::{ CacheDirOperations, CacheRoot, CacheRoots };
+
+// The rules of Windows
+pub struct WindowsCache;
+
+impl CacheDirOperations for WindowsCache {
+    fn app_cache_roots(&self, env: &dyn Environment, app_cache_dir: &Path) -> CacheRoots {
+        match env.current_dir() {
+            Ok(current_dir) => {
+                vec![Ok(CacheRoot::new(super::join(TargetOs::Windows, &current_dir, app_cache_dir),
+                                       true))]
+            },
+            Err(err)        => {
+                vec![Err(Attempt::new(CacheKind::App, None,
+                                      io::Error::new(err.kind(),
+                                                     format!("Could not obtain the \
+                                                             current directory: {}", err))))]
+            }
         }
-
-        super::create_dir_helper(&[app_cache_dir], &cache_name)
     }
 
-    fn create_user_cache_dir(cache_name: &Path)   -> io::Result<PathBuf> {
+    fn user_cache_roots(&self, env: &dyn Environment)   -> CacheRoots {
         // We try(and fallback to the next if it fails):
-        // 1. Windows environment variable: %LOCALAPPDATA%
-        // 2. %APPDATA%
-        // 3. Rust's `home_dir` which returns %HOME% -ifndef-> %USERPROFILE% -ifndef-> OS syscall.
-        //    Currently `home_dir` has a bug and can return an empty string, so we check that too.
-        //    Since the user's home directory is not a dedicated cache dir, we try to add the "Cache" dir
-        let mut errors_buffer = String::new();
-        let mut cache_dirs: Vec<PathBuf> = Vec::with_capacity(3);
-
-        fn add_env_path(errors_buffer: &mut String,
-                        cache_dirs:    &mut Vec<PathBuf>,
-                        env_var:       &str) {
-            env::var_os(env_var)
-                .or_else(|| {
-                    errors_buffer.push_str(&format!("\n[User Cache]: %{}% is undefined", env_var));
-                    None
-                })
-                .map(|path| {
-                    if !path.is_empty() {
-                        cache_dirs.push(PathBuf::from(path))
-                    } else {
-                        errors_buffer.push_str(&format!("\n[User Cache]: %{}% is defined, \
-                                                        but it is set to an empty string", env_var))
-                    }
-                });
-        }
-
-        add_env_path(&mut errors_buffer, &mut cache_dirs, "LOCALAPPDATA");
-        add_env_path(&mut errors_buffer, &mut cache_dirs, "APPDATA");
-
-        env::home_dir()
-            .or_else(|| {
-                errors_buffer.push_str(
-                    &format!("\n[User Cache]: Could not obtain user's home directory"));
-                None
-            })
-            .and_then(|path| {
-                if !path.as_os_str().is_empty() {
-                    Some(path.join("Cache"))
-                } else {
-                    errors_buffer.push_str(
-                        &format!("\n[User Cache]: %HOME% and/or %USERPROFILE% variables \
-                        are/is defined but are/is set to an empty string"));
-                    None
-                }
-            })
-            .map(|path| {
-                if let Err(err) = fs::create_dir_all(&path) {
-                    errors_buffer.push_str(
-                        &format!("\n{}\n[User Cache][{:?}]: \
-                                 Failed to create the parent cache directory: {}",
-                                 err.description(), err.kind(), path.display()));
-                } else {
-                    cache_dirs.push(path);
-                }
-            });
-
-        if cache_dirs.is_empty() {
-            return Err(
-                io::Error::new(
-                    io::ErrorKind::NotFound,
-                    format!("{}\n[User Cache]: Could not obtain user's cache directory",
-                            errors_buffer)
-                )
-            );
-        }
+        // 1. The `LocalAppData` known folder -ifndef-> %LOCALAPPDATA%.
+        //    The roaming %APPDATA% is never used, because it is synced to the domain profiles
+        // 2. The user's profile directory: the `Profile` known folder -ifndef-> %USERPROFILE%.
+        //    Since the user's home directory is not a dedicated cache dir, we try to add
+        //    the "Cache" dir
+        let mut roots: CacheRoots = Vec::with_capacity(2);
+
+        roots.push(match known_folder(env, KnownFolder::LocalAppData, "LOCALAPPDATA") {
+            Ok(path)    => Ok(CacheRoot::new(path, false)),
+            Err(reason) => super::unavailable(CacheKind::User, &reason)
+        });
+
+        roots.push(match super::home_dir(TargetOs::Windows, env) {
+            Ok(home_dir) => {
+                let cache_dir = super::join(TargetOs::Windows, &home_dir, Path::new("Cache"));
+                Ok(CacheRoot::new(cache_dir, true))
+            },
+            Err(reason)  => super::unavailable(CacheKind::User, &reason)
+        });
+
+        roots
+    }
 
-        match super::create_dir_helper(&cache_dirs, &cache_name) {
-            Ok(result) => Ok(result),
-            Err(err)   => Err(io::Error::new(err.kind(),
-                                             format!("{}\n{}", errors_buffer, err.description())))
+    fn system_cache_roots(&self, env: &dyn Environment) -> CacheRoots {
+        match known_folder(env, KnownFolder::ProgramData, "ProgramData") {
+            Ok(path)    => vec![Ok(CacheRoot::new(path, false))],
+            Err(reason) => vec![super::unavailable(CacheKind::System, &reason)]
         }
     }
 
-    fn create_system_cache_dir(cache_name: &Path) -> io::Result<PathBuf> {
-        use std::mem;
-
-        // Initialized and used only when `program_data` is `None`
-        let mut error: String = unsafe { mem::uninitialized() };
-        let program_data = env::var_os("ProgramData")
-                               .or_else(|| {
-                                   error = String::from("%ProgramData% is not set");
-                                   None
-                               })
-                               .and_then(|path| {
-                                   if path.as_os_str().is_empty() {
-                                       error = String::from("%ProgramData% is set but it is empty");
-                                       None
-                                   } else {
-                                       Some(path)
-                                   }
-                               });
+    fn tmp_cache_roots(&self, env: &dyn Environment)    -> CacheRoots {
+        let temp_dir = env.temp_dir();
 
-        if program_data.is_none() {
-            Err(io::Error::new(io::ErrorKind::NotFound,
-                               format!("[System Cache]: Could not obtain the path to the cache \
-                                        because {}", error)))
+        if temp_dir.as_os_str().is_empty() {
+            vec![super::unavailable(CacheKind::Tmp,
+                                    "Could not obtain the temporary directory's path")]
         } else {
-            let program_data = PathBuf::from(program_data.unwrap());
-            super::create_dir_helper(&[program_data], &cache_name)
+            vec![Ok(CacheRoot::new(temp_dir, false))]
         }
     }
 
-    fn create_tmp_cache_dir(cache_name: &Path)    -> io::Result<PathBuf> {
-        let temp_dir = env::temp_dir();
+    fn memory_cache_roots(&self, _: &dyn Environment)   -> CacheRoots {
+        vec![super::unavailable(CacheKind::Memory, "Memory caches are not supported on Windows")]
+    }
 
-        if temp_dir.as_os_str().is_empty() {
-            Err(io::Error::new(io::ErrorKind::NotFound,
-                               "[Tmp Cache]: Could not obtain the temporary directory's path"))
-        } else {
-            super::create_dir_helper(&[temp_dir], &cache_name)
-        }
+    fn user_segment(&self, env: &dyn Environment)       -> Option<OsString> {
+        // The SID is used instead of `%USERNAME%`, because it can't be spoofed by changing
+        // the environment variables and it is unique even between the domains
+        env.user_sid().filter(|sid| !sid.is_empty()).map(OsString::from)
+    }
+}
+
+// Obtains a known folder from the OS and falls back to its environment variable,
+// which can be missing or spoofed(ex: in services)
+fn known_folder(env: &dyn Environment, folder: KnownFolder, var: &str) -> Result<PathBuf, String> {
+    if let Some(path) = env.known_folder(folder).filter(|path| !path.as_os_str().is_empty()) {
+        return Ok(path);
     }
 
-    fn create_memory_cache_dir(_: &Path)          -> io::Result<PathBuf> {
-        Err(io::Error::new(io::ErrorKind::NotFound,
-                           "[Memory Cache]: Memory caches are not supported on Windows"))
+    match env.var_os(var) {
+        Some(ref path) if !path.is_empty() => Ok(PathBuf::from(path)),
+        Some(_) => Err(format!("The {:?} known folder is not available and %{}% is defined, \
+                               but it is set to an empty string", folder, var)),
+        None    => Err(format!("The {:?} known folder is not available and %{}% is undefined",
+                               folder, var))
     }
 }
@@ -0,0 +1,85 @@
+use std::fmt;
+
+/// The operating systems whose cache conventions `cachedir` knows about.
+///
+/// The conventions of any of them can be used from any host(ex: [`AppIdentity::cache_name`]
+/// computes the macOS name of an application while running on Linux), while
+/// `CacheDirConfig` always follows the conventions of [`TargetOs::current`].
+///
+/// [`AppIdentity::cache_name`]: struct.AppIdentity.html#method.cache_name
+/// [`TargetOs::current`]: enum.TargetOs.html#method.current
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TargetOs {
+    /// Linux(the XDG Base Directory Specification and the RAM-backed `/dev/shm`).
+    Linux,
+    /// macOS(the `Library/Caches` directories and reverse-DNS bundle identifiers).
+    MacOs,
+    /// Windows(the `AppData\Local` and `ProgramData` directories).
+    Windows,
+    /// Redox.
+    Redox,
+    /// Emscripten, which can create files anywhere in its virtual file system.
+    Emscripten,
+    /// The other Unix-like systems(ex: FreeBSD, Android), which follow the XDG Base Directory
+    /// Specification, but don't have a RAM-backed directory for the memory caches.
+    OtherUnix,
+    /// An operating system without known cache locations.
+    Unknown
+}
+
+impl TargetOs {
+    /// The operating system for which `cachedir` was compiled.
+    ///
+    /// ```
+    /// # use cachedir::TargetOs;
+    /// if cfg!(target_os = "linux") {
+    ///     assert_eq!(TargetOs::Linux, TargetOs::current());
+    /// }
+    /// ```
+    pub fn current() -> TargetOs {
+        if cfg!(target_os = "linux") {
+            TargetOs::Linux
+        } else if cfg!(target_os = "macos") {
+            TargetOs::MacOs
+        } else if cfg!(windows) {
+            TargetOs::Windows
+        } else if cfg!(target_os = "redox") {
+            TargetOs::Redox
+        } else if cfg!(target_os = "emscripten") {
+            TargetOs::Emscripten
+        } else if cfg!(unix) {
+            TargetOs::OtherUnix
+        } else {
+            TargetOs::Unknown
+        }
+    }
+
+    /// The character that separates the components of the paths(`\` on Windows and `/`
+    /// on the other systems).
+    ///
+    /// ```
+    /// # use cachedir::TargetOs;
+    /// assert_eq!('\\', TargetOs::Windows.separator());
+    /// assert_eq!('/', TargetOs::MacOs.separator());
+    /// ```
+    pub fn separator(&self) -> char {
+        match *self {
+            TargetOs::Windows => '\\',
+            _                 => '/'
+        }
+    }
+}
+
+impl fmt::Display for TargetOs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            TargetOs::Linux      => "Linux",
+            TargetOs::MacOs      => "macOS",
+            TargetOs::Windows    => "Windows",
+            TargetOs::Redox      => "Redox",
+            TargetOs::Emscripten => "Emscripten",
+            TargetOs::OtherUnix  => "Unix",
+            TargetOs::Unknown    => "Unknown OS"
+        })
+    }
+}
@@ -2,7 +2,9 @@ use super::CacheDir;
 
 use std::ops;
 use std::borrow::Borrow;
+use std::cmp;
 use std::convert;
+use std::hash;
 
 use std::path;
 use std::ffi;
@@ -15,15 +17,47 @@ impl ops::Deref for CacheDir {
     }
 }
 
-impl convert::Into<path::PathBuf> for CacheDir {
-    fn into(self) -> path::PathBuf {
-        self.path
+impl convert::From<CacheDir> for path::PathBuf {
+    fn from(cache_dir: CacheDir) -> path::PathBuf {
+        cache_dir.path
     }
 }
 
-impl convert::Into<ffi::OsString> for CacheDir {
-    fn into(self) -> ffi::OsString {
-        self.path.into()
+impl convert::From<CacheDir> for ffi::OsString {
+    fn from(cache_dir: CacheDir) -> ffi::OsString {
+        cache_dir.path.into()
+    }
+}
+
+// Two `CacheDir`s are the same if they have the same path, regardless of how they were
+// obtained(ex: the free space of the file system could be different between the calls),
+// so they can be looked up by path in the collections.
+// `Borrow<OsStr>` is kept for compatibility, but `OsStr` is hashed and ordered differently
+// than `Path`(byte by byte instead of component by component), so the collections should be
+// looked up by `Path` instead
+impl PartialEq for CacheDir {
+    fn eq(&self, other: &CacheDir) -> bool {
+        self.path == other.path
+    }
+}
+
+impl Eq for CacheDir {}
+
+impl hash::Hash for CacheDir {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.path.hash(state)
+    }
+}
+
+impl PartialOrd for CacheDir {
+    fn partial_cmp(&self, other: &CacheDir) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CacheDir {
+    fn cmp(&self, other: &CacheDir) -> cmp::Ordering {
+        self.path.cmp(&other.path)
     }
 }
 
@@ -68,6 +102,6 @@ impl<'a> IntoIterator for &'a CacheDir {
     type IntoIter = path::Iter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.path.into_iter()
+        self.path.iter()
     }
 }
@@ -1,132 +1,1839 @@
 extern crate cachedir;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(windows)]
+extern crate winapi;
 
-use cachedir::CacheDirConfig;
+use cachedir::{ AppIdentity, CacheDir, CacheDirConfig, CacheDirError, CacheKind, CacheNameError,
+                CandidateStatus, CustomEnvironment, Environment, FsType, KnownFolder, LockMode,
+                Persistence, ProcessEnvironment, TargetOs };
 
 use std::env;
-use std::path::PathBuf;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops;
+use std::path::{ Path, PathBuf };
+use std::process;
+use std::thread;
+
+// An environment where only the identity of the current user is known(the uid on Unix and
+// the SID on Windows), so the tests don't depend on(and don't modify) the environment of
+// the test process
+fn fake_env() -> CustomEnvironment {
+    let mut environment = CustomEnvironment::new();
+    if let Some(uid) = ProcessEnvironment.uid() {
+        environment.set_uid(uid);
+    }
+    if let Some(user_sid) = ProcessEnvironment.user_sid() {
+        environment.set_user_sid(&user_sid);
+    }
+    environment
+}
+
+// A name that is unique to the test and to the test process, for the tests that write into
+// the shared directories(ex: `/var/tmp` and `/dev/shm`), so they don't collide with
+// the leftovers of another run
+fn unique_name(test_name: &str) -> String {
+    format!("__cachedir_test_{}_{}", test_name, process::id())
+}
+
+// Removes the paths created by a test when it ends, even if it fails, so the tests leave
+// nothing behind in the shared directories
+struct RemoveOnDrop(Vec<PathBuf>);
+
+impl RemoveOnDrop {
+    fn new() -> RemoveOnDrop {
+        RemoveOnDrop(Vec::new())
+    }
+
+    fn push<P: AsRef<Path>>(&mut self, path: P) {
+        self.0.push(path.as_ref().to_path_buf());
+    }
+}
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        for path in &self.0 {
+            remove_path(path);
+        }
+    }
+}
+
+// Removes a directory with its contents, a file or a symbolic link(without following it)
+fn remove_path(path: &Path) {
+    match fs::symlink_metadata(path) {
+        Ok(ref metadata) if metadata.is_dir() => { let _ = fs::remove_dir_all(path); },
+        Ok(_)                                  => { let _ = fs::remove_file(path); },
+        Err(_)                                 => {}
+    }
+}
+
+// A fresh, uniquely named path in the temporary directory, for the files of a test.
+// Nothing exists at the path when the test starts and it is removed when the test ends
+struct TestDir(PathBuf);
+
+impl TestDir {
+    fn new(test_name: &str) -> TestDir {
+        let path = env::temp_dir().join(unique_name(test_name));
+        remove_path(&path);
+        TestDir(path)
+    }
+
+    // Creates the cache directory `cache_name`, with this directory as the application cache
+    fn app_cache(&self, cache_name: &str) -> CacheDir {
+        CacheDirConfig::new(cache_name)
+                       .app_cache_path(&self.0)
+                       .get_cache_dir().unwrap()
+    }
+}
+
+impl ops::Deref for TestDir {
+    type Target = PathBuf;
+
+    fn deref(&self) -> &PathBuf {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TestDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<OsStr> for TestDir {
+    fn as_ref(&self) -> &OsStr {
+        self.0.as_os_str()
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        remove_path(&self.0);
+    }
+}
 
 #[test]
-#[cfg(any(unix, target_os = "redox"))]
+#[cfg(unix)]
 fn create_user_cache() {
-    let expected_cache_dir = env::home_dir()
-                                 .and_then(|path| {
-                                     if path.as_os_str().is_empty() {
-                                         None
-                                     } else {
-                                         if cfg!(not(target_os = "macos")) {
-                                             Some(path.join(".cache"))
-                                         } else {
-                                             Some(path.join("Library/Caches"))
-                                         }
-                                     }
-                                 })
-                                 .or_else(|| {
-                                     if cfg!(target_os = "emscripten") {
-                                         Some(PathBuf::from("/var/cache"))
-                                     } else {
-                                         None
-                                     }
-                                 });
-
-    if expected_cache_dir.is_none() {
-        // This test will fail in this case, so we will ignore it
+    let home_dir = TestDir::new("home");
+    let mut environment = fake_env();
+    environment.set_home_dir(&home_dir);
+
+    let cache_dir: PathBuf = CacheDirConfig::new("__cachedir_test_create_user_cache")
+                                            .environment(&environment)
+                                            .get_cache_dir().unwrap()
+                                            .into();
+    let expected_cache_dir = if cfg!(target_os = "macos") {
+        home_dir.join("Library/Caches")
     } else {
-        let expected_cache_dir = expected_cache_dir.unwrap()
-                                                   .join("__cachedir_test_create_user_cache");
-        let cache_dir: PathBuf = CacheDirConfig::new("__cachedir_test_create_user_cache")
-                                                .get_cache_dir().unwrap()
-                                                .into();
-        assert_eq!(expected_cache_dir, cache_dir);
-    }
+        home_dir.join(".cache")
+    };
+    assert_eq!(expected_cache_dir.join("__cachedir_test_create_user_cache"), cache_dir);
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "emscripten")))]
+fn create_user_cache_without_home_dir() {
+    let err = CacheDirConfig::new("__cachedir_test_create_user_cache")
+                             .environment(&fake_env())
+                             .get_cache_dir().unwrap_err();
+    assert_eq!(io::ErrorKind::NotFound, err.kind());
+    assert_eq!(None, err.attempts()[0].path());
+
+    // The error tells which sources of the home directory were tried
+    let reason = err.attempts()[0].error().to_string();
+    assert!(reason.contains("$HOME is not set"), "{}", reason);
+    assert!(reason.contains("passwd"), "{}", reason);
+}
+
+#[test]
+fn app_cache_path_is_relative_to_the_current_dir() {
+    let current_dir = TestDir::new("current_dir");
+    let mut environment = fake_env();
+    environment.set_current_dir(&current_dir);
+
+    let cache_dir = CacheDirConfig::new("__cachedir_test_app_cache")
+                                   .environment(&environment)
+                                   .app_cache_path("relative")
+                                   .get_cache_dir().unwrap();
+    assert_eq!(current_dir.join("relative").join("__cachedir_test_app_cache"),
+               cache_dir.into_path_buf());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn create_user_cache_in_xdg_cache_home() {
+    let xdg_cache_home = TestDir::new("xdg_cache_home");
+    let mut environment = fake_env();
+    environment.set_var("XDG_CACHE_HOME", &xdg_cache_home);
+
+    let cache_dir: PathBuf = CacheDirConfig::new("__cachedir_test_xdg_user_cache")
+                                            .environment(&environment)
+                                            .get_cache_dir().unwrap()
+                                            .into();
+    assert_eq!(xdg_cache_home.join("__cachedir_test_xdg_user_cache"), cache_dir);
+    assert!(cache_dir.is_dir());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn create_user_cache_with_empty_xdg_cache_home() {
+    let home_dir = TestDir::new("home");
+    let mut environment = fake_env();
+    environment.set_var("XDG_CACHE_HOME", "").set_home_dir(&home_dir);
+
+    let cache_dir: PathBuf = CacheDirConfig::new("__cachedir_test_create_user_cache")
+                                            .environment(&environment)
+                                            .get_cache_dir().unwrap()
+                                            .into();
+    assert_eq!(home_dir.join(".cache").join("__cachedir_test_create_user_cache"), cache_dir);
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn create_user_cache_rejects_relative_xdg_cache_home() {
+    let mut environment = fake_env();
+    environment.set_var("XDG_CACHE_HOME", "relative/cache");
+
+    let err = CacheDirConfig::new("__cachedir_test_create_user_cache")
+                             .environment(&environment)
+                             .get_cache_dir().unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    assert!(err.to_string().contains("XDG_CACHE_HOME"));
+    assert!(!Path::new("relative/cache").exists());
 }
 
 #[test]
 #[cfg(windows)]
 fn create_user_cache() {
-    let mut cache_dirs: Vec<PathBuf> = Vec::with_capacity(3);
+    let local_app_data = TestDir::new("local_app_data");
+    fs::create_dir_all(&local_app_data).unwrap();
+    let mut environment = CustomEnvironment::new();
+    environment.set_var("LOCALAPPDATA", &local_app_data);
+
+    let cache_dir: PathBuf = CacheDirConfig::new("__cachedir_test_create_user_cache")
+                                            .environment(&environment)
+                                            .get_cache_dir().unwrap()
+                                            .into();
+    assert_eq!(local_app_data.join("__cachedir_test_create_user_cache"), cache_dir);
+}
+
+#[test]
+#[cfg(any(unix, windows))]
+fn create_tmp_cache() {
+    let cache_name = unique_name("create_tmp_cache");
+    let mut cleanup = RemoveOnDrop::new();
+
+    let cache_dir = CacheDirConfig::new(&cache_name)
+                                   .tmp_cache(true)
+                                   .get_cache_dir().unwrap();
+    cleanup.push(&cache_dir);
+    // The temporary directory of Windows already belongs to the current user
+    let expected_cache_dir = if cfg!(unix) {
+        Path::new("/var/tmp").join(cache_dir.user_segment().unwrap()).join(&cache_name)
+    } else {
+        env::temp_dir().join(&cache_name)
+    };
+    assert_eq!(expected_cache_dir, cache_dir.into_path_buf());
+}
+
+// `/dev/shm` is missing or read-only in some containers
+#[cfg(target_os = "linux")]
+fn dev_shm_is_writable() -> bool {
+    let path = ::std::ffi::CString::new("/dev/shm").unwrap();
+    unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 }
+}
 
-    fn add_env_path(cache_dirs:    &mut Vec<PathBuf>,
-                    env_var:       &str) {
-        env::var_os(env_var)
-            .map(|path| {
-                if !path.is_empty() {
-                    cache_dirs.push(PathBuf::from(path))
-                }
-            });
+#[test]
+#[cfg(target_os = "linux")]
+fn create_mem_cache() {
+    if !dev_shm_is_writable() {
+        eprintln!("create_mem_cache skipped: /dev/shm is missing or not writable");
+        return;
     }
 
-    add_env_path(&mut cache_dirs, "LOCALAPPDATA");
-    add_env_path(&mut cache_dirs, "APPDATA");
+    let cache_name = unique_name("create_mem_cache");
+    let mut cleanup = RemoveOnDrop::new();
 
-    env::home_dir()
-        .map(|path| {
-            if !path.as_os_str().is_empty() {
-                cache_dirs.push(path.join("Cache"))
-            }
-        });
+    let cache_dir = CacheDirConfig::new(&cache_name)
+                                   .environment(&fake_env())
+                                   .mem_cache(true)
+                                   .get_cache_dir().unwrap();
+    cleanup.push(&cache_dir);
+    let expected_cache_dir = Path::new("/dev/shm").join(cache_dir.user_segment().unwrap())
+                                                  .join(&cache_name);
+    assert_eq!(expected_cache_dir, cache_dir.into_path_buf());
+}
+
+// The other memory cache tests also require `/dev/shm` to be a tmpfs: run them with
+// `cargo test -- --ignored` where it is(the usual setup of Linux, but not of every container)
+#[test]
+#[cfg(target_os = "linux")]
+#[ignore = "requires a writable tmpfs on /dev/shm"]
+fn create_mem_cache_in_xdg_runtime_dir() {
+    // `/dev/shm` is used as a stand-in for `/run/user/<uid>`, since both are usually a tmpfs
+    let runtime_dir = Path::new("/dev/shm").join(unique_name("xdg_runtime_dir"));
+    let mut cleanup = RemoveOnDrop::new();
+    cleanup.push(&runtime_dir);
+    fs::create_dir_all(&runtime_dir).expect("/dev/shm must be writable");
+
+    let mut environment = fake_env();
+    environment.set_var("XDG_RUNTIME_DIR", &runtime_dir);
+
+    let cache_dir = CacheDirConfig::new("__cachedir_test_runtime_cache")
+                                   .environment(&environment)
+                                   .mem_cache(true)
+                                   .get_cache_dir().unwrap();
+    assert_eq!(runtime_dir.join("__cachedir_test_runtime_cache"), cache_dir.as_path());
+
+    // `$XDG_RUNTIME_DIR` already belongs to the current user
+    assert_eq!(None, cache_dir.user_segment());
+}
+
+// The target directory is usually on a disk, but it can be on a tmpfs(ex: in a CI container)
+#[test]
+#[cfg(target_os = "linux")]
+#[ignore = "requires the target directory to be stored on a disk"]
+fn xdg_runtime_dir_is_skipped_if_not_in_ram() {
+    let runtime_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("__cachedir_test_runtime_disk");
+    fs::create_dir_all(&runtime_dir).unwrap();
+
+    let mut environment = fake_env();
+    environment.set_var("XDG_RUNTIME_DIR", &runtime_dir);
+
+    let candidates = CacheDirConfig::new("__cachedir_test_runtime_cache")
+                                    .environment(&environment)
+                                    .mem_cache(true)
+                                    .candidates().unwrap();
+    let result = CacheDirConfig::new("__cachedir_test_runtime_cache")
+                                .environment(&environment)
+                                .mem_cache(true)
+                                .get_cache_dir();
+
+    let candidate = candidates.iter()
+                              .find(|candidate| candidate.path().starts_with(&runtime_dir))
+                              .unwrap();
+    assert_eq!(CacheKind::Memory, candidate.kind());
+    assert_eq!(CandidateStatus::NotRamBacked, candidate.status(),
+               "the target directory must be stored on a disk");
+    assert!(candidate.reason().unwrap().contains("tmpfs or ramfs"));
+    match result {
+        Ok(cache_dir) => {
+            let _cleanup = RemoveOnDrop(vec![cache_dir.to_path_buf()]);
+            assert!(!cache_dir.starts_with(&runtime_dir));
+        },
+        Err(err)      => {
+            assert_eq!(Some(runtime_dir.as_path()), err.attempts()[0].path());
+            assert_eq!(io::ErrorKind::Other, err.attempts()[0].error().kind());
+        }
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+#[ignore = "requires a writable tmpfs on /dev/shm"]
+fn mem_cache_reports_fs_info() {
+    let cache_name = unique_name("mem_fs_info");
+    let mut cleanup = RemoveOnDrop::new();
+
+    let cache_dir = CacheDirConfig::new(&cache_name)
+                                   .environment(&fake_env())
+                                   .mem_cache(true)
+                                   .mem_cache_ram_only(true)
+                                   .get_cache_dir().unwrap();
+    cleanup.push(&cache_dir);
+    assert!(cache_dir.starts_with("/dev/shm"));
+    let fs_info = cache_dir.fs_info().unwrap();
+    assert!(fs_info.is_ram_backed());
+    assert_eq!(FsType::Tmpfs, fs_info.fs_type());
+
+    // The user cache is not checked
+    let home_dir = env::temp_dir().join(unique_name("mem_fs_info_home"));
+    cleanup.push(&home_dir);
+    let mut environment = fake_env();
+    environment.set_home_dir(&home_dir);
+    let cache_dir = CacheDirConfig::new(&cache_name)
+                                   .environment(&environment)
+                                   .get_cache_dir().unwrap();
+    assert_eq!(None, cache_dir.fs_info());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+#[ignore = "requires a writable tmpfs on /dev/shm"]
+fn mem_cache_min_free_space_is_enforced() {
+    let cache_name = unique_name("mem_free_space");
+    let mut cleanup = RemoveOnDrop::new();
+    let environment = fake_env();
+
+    let err = CacheDirConfig::new(&cache_name)
+                             .environment(&environment)
+                             .mem_cache(true)
+                             .mem_cache_min_free_space(u64::MAX)
+                             .get_cache_dir().unwrap_err();
+    assert_eq!(Some(Path::new("/dev/shm")), err.attempts()[0].path());
+    assert_eq!(io::ErrorKind::Other, err.attempts()[0].error().kind());
+    assert!(err.attempts()[0].error().to_string().contains("bytes are required"));
+
+    let cache_dir = CacheDirConfig::new(&cache_name)
+                                   .environment(&environment)
+                                   .mem_cache(true)
+                                   .mem_cache_min_free_space(1)
+                                   .get_cache_dir().unwrap();
+    cleanup.push(&cache_dir);
+    assert!(cache_dir.starts_with("/dev/shm"));
+    assert!(cache_dir.fs_info().unwrap().free_space() >= 1);
+}
+
+#[test]
+fn min_free_space_skips_full_locations() {
+    let app_cache_path = TestDir::new("min_free_space");
+
+    let err = CacheDirConfig::new("__cachedir_test_min_free_space")
+                             .app_cache_path(&app_cache_path)
+                             .tmp_cache(true)
+                             .min_free_space(u64::MAX)
+                             .get_cache_dir().unwrap_err();
+    assert_eq!(CacheKind::App, err.attempts()[0].kind());
+    assert_eq!(Some(app_cache_path.as_path()), err.attempts()[0].path());
+    for attempt in err.attempts() {
+        if attempt.path().is_some() {
+            assert_eq!(io::ErrorKind::Other, attempt.error().kind());
+            assert!(attempt.error().to_string().contains("bytes are required"));
+        }
+    }
+
+    // Nothing was created for the skipped locations
+    assert!(!app_cache_path.exists());
+
+    let cache_dir = CacheDirConfig::new("__cachedir_test_min_free_space")
+                                   .app_cache_path(&app_cache_path)
+                                   .min_free_space(1)
+                                   .get_cache_dir().unwrap();
+    assert_eq!(app_cache_path.join("__cachedir_test_min_free_space"), cache_dir.as_path());
+    assert!(cache_dir.fs_info().unwrap().free_space() >= 1);
+}
+
+#[test]
+#[cfg(unix)]
+fn probe_writable_skips_read_only_directories() {
+    use std::os::unix::fs::PermissionsExt;
 
-    if cache_dirs.is_empty() {
-        // This test will fail in this case, so we will ignore it
+    let app_cache_path = TestDir::new("probe_writable");
+    let read_only_dir = app_cache_path.join("__cachedir_test_probe");
+    fs::create_dir_all(&read_only_dir).unwrap();
+    fs::set_permissions(&read_only_dir, fs::Permissions::from_mode(0o555)).unwrap();
+
+    // Without the probe, the existing directory is returned even if it is read-only
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_probe");
+    assert_eq!(read_only_dir, cache_dir.as_path());
+
+    // The permissions are ignored for `root`
+    let is_writable = fs::File::create(read_only_dir.join("file")).is_ok();
+    let _ = fs::remove_file(read_only_dir.join("file"));
+
+    let err = CacheDirConfig::new("__cachedir_test_probe")
+                             .app_cache_path(&app_cache_path)
+                             .probe_writable(true)
+                             .get_cache_dir().err();
+    if is_writable {
+        assert!(err.is_none());
     } else {
-        let expected_cache_dir = cache_dirs[0].join("__cachedir_test_create_user_cache");
-        let cache_dir: PathBuf = CacheDirConfig::new("__cachedir_test_create_user_cache")
-                                                .get_cache_dir().unwrap()
-                                                .into();
-        assert_eq!(expected_cache_dir, cache_dir);
+        let err = err.unwrap();
+        assert_eq!(Some(read_only_dir.as_path()), err.attempts()[0].path());
+        assert_eq!(io::ErrorKind::PermissionDenied, err.attempts()[0].error().kind());
     }
+
+    // The probe file is removed
+    assert_eq!(0, fs::read_dir(&read_only_dir).unwrap().count());
+    fs::set_permissions(&read_only_dir, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+// Writes a file of `size` bytes that was last used `age` seconds ago
+fn write_cache_file(path: &Path, size: usize, age: u64) {
+    use std::time::{ SystemTime, UNIX_EPOCH };
+
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, vec![0u8; size]).unwrap();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    set_file_times(path, now - age);
+}
+
+// Sets the access and modification times of a file, in seconds since the Unix epoch
+#[cfg(unix)]
+fn set_file_times(path: &Path, secs: u64) {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes()).unwrap();
+    let time = libc::timeval { tv_sec: secs as libc::time_t, tv_usec: 0 };
+    assert_eq!(0, unsafe { libc::utimes(path.as_ptr(), [time, time].as_ptr()) });
+}
+
+#[cfg(windows)]
+fn set_file_times(path: &Path, secs: u64) {
+    use std::os::windows::io::AsRawHandle;
+    use std::ptr;
+    use winapi::shared::minwindef::FILETIME;
+    use winapi::um::fileapi::SetFileTime;
+
+    // `FILETIME` counts the intervals of 100 nanoseconds since 1601-01-01
+    let intervals = (secs + 11_644_473_600) * 10_000_000;
+    let time = FILETIME { dwLowDateTime:  intervals as u32,
+                          dwHighDateTime: (intervals >> 32) as u32 };
+    let file = fs::OpenOptions::new().write(true).open(path).unwrap();
+    assert_ne!(0, unsafe { SetFileTime(file.as_raw_handle() as _, ptr::null(), &time, &time) });
+}
+
+#[test]
+fn enforce_limit_removes_least_recently_used_files() {
+    let app_cache_path = TestDir::new("enforce_limit");
+
+    let cache_dir = CacheDirConfig::new("__cachedir_test_enforce_limit")
+                                   .app_cache_path(&app_cache_path)
+                                   .cachedir_tag(true)
+                                   .size_limit(250)
+                                   .get_cache_dir().unwrap();
+    assert_eq!(Some(250), cache_dir.size_limit());
+
+    write_cache_file(&cache_dir.join("oldest"), 100, 300);
+    write_cache_file(&cache_dir.join("nested").join("old"), 100, 200);
+    write_cache_file(&cache_dir.join("newest"), 100, 100);
+    assert_eq!(300, cache_dir.size().unwrap());
+
+    let report = cache_dir.enforce_limit().unwrap();
+    assert_eq!(&[cache_dir.join("oldest")], report.removed());
+    assert_eq!(1, report.files_removed());
+    assert_eq!(100, report.bytes_freed());
+    assert_eq!(200, report.remaining_size());
+    assert!(report.errors().is_empty());
+
+    // The limit is already respected and the tag is never removed
+    let report = cache_dir.enforce_limit().unwrap();
+    assert_eq!(0, report.files_removed());
+    assert!(cache_dir.is_tagged());
+    assert!(cache_dir.join("nested").join("old").exists());
+}
+
+#[test]
+fn enforce_limit_without_a_limit_removes_nothing() {
+    let app_cache_path = TestDir::new("enforce_no_limit");
+
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_enforce_limit");
+    write_cache_file(&cache_dir.join("file"), 100, 100);
+
+    let report = cache_dir.enforce_limit().unwrap();
+    assert_eq!(None, cache_dir.size_limit());
+    assert_eq!(0, report.files_removed());
+    assert_eq!(100, report.remaining_size());
+}
+
+#[test]
+#[cfg(unix)]
+fn enforce_limit_does_not_follow_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let app_cache_path = TestDir::new("enforce_symlinks");
+    let outside_dir = TestDir::new("enforce_outside");
+    write_cache_file(&outside_dir.join("file"), 100, 1000);
+
+    let cache_dir = CacheDirConfig::new("__cachedir_test_enforce_limit")
+                                   .app_cache_path(&app_cache_path)
+                                   .size_limit(0)
+                                   .get_cache_dir().unwrap();
+    symlink(&outside_dir, cache_dir.join("link")).unwrap();
+    assert_eq!(0, cache_dir.size().unwrap());
+
+    let report = cache_dir.enforce_limit().unwrap();
+    assert_eq!(0, report.files_removed());
+    assert!(outside_dir.join("file").exists());
+}
+
+#[test]
+fn prune_removes_stale_files_and_empty_directories() {
+    use std::time::Duration;
+
+    let app_cache_path = TestDir::new("prune");
+
+    let cache_dir = CacheDirConfig::new("__cachedir_test_prune")
+                                   .app_cache_path(&app_cache_path)
+                                   .cachedir_tag(true)
+                                   .get_cache_dir().unwrap();
+    write_cache_file(&cache_dir.join("stale"), 100, 10 * 24 * 60 * 60);
+    write_cache_file(&cache_dir.join("a").join("b").join("stale"), 50, 10 * 24 * 60 * 60);
+    write_cache_file(&cache_dir.join("fresh"), 10, 60);
+    fs::create_dir_all(cache_dir.join("empty")).unwrap();
+
+    let report = cache_dir.prune(Duration::from_secs(24 * 60 * 60)).unwrap();
+    assert_eq!(2, report.files_removed());
+    assert_eq!(150, report.bytes_freed());
+    assert_eq!(10, report.remaining_size());
+    assert_eq!(3, report.dirs_removed());
+    assert!(report.errors().is_empty());
+
+    assert!(cache_dir.join("fresh").exists());
+    assert!(!cache_dir.join("a").exists());
+    assert!(!cache_dir.join("empty").exists());
+    assert!(cache_dir.is_tagged());
+
+    // Nothing is older than this
+    let report = cache_dir.prune(Duration::from_secs(u64::MAX)).unwrap();
+    assert_eq!(0, report.files_removed());
+}
+
+#[test]
+#[cfg(unix)]
+fn prune_does_not_follow_symlinks() {
+    use std::os::unix::fs::symlink;
+    use std::time::Duration;
+
+    let app_cache_path = TestDir::new("prune_symlinks");
+    let outside_dir = TestDir::new("prune_outside");
+    write_cache_file(&outside_dir.join("file"), 100, 1000);
+
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_prune");
+    symlink(&outside_dir, cache_dir.join("link")).unwrap();
+
+    let report = cache_dir.prune(Duration::from_secs(0)).unwrap();
+    assert_eq!(0, report.files_removed());
+    assert!(outside_dir.join("file").exists());
+    assert!(fs::symlink_metadata(cache_dir.join("link")).is_ok());
+}
+
+#[test]
+fn entries_can_be_stored_and_removed() {
+    let app_cache_path = TestDir::new("entries");
+
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_entries");
+    let keys: [&[u8]; 4] = [b"key", b"../../etc/passwd", b"", &[0, 255, b'/', b'\\']];
+
+    for key in &keys {
+        assert_eq!(None, cache_dir.get(key).unwrap());
+        assert!(!cache_dir.contains(key).unwrap());
+
+        cache_dir.put(key, key).unwrap();
+        assert_eq!(Some(key.to_vec()), cache_dir.get(key).unwrap());
+        assert!(cache_dir.contains(key).unwrap());
+
+        // The entries are stored in `<cache_dir>/ab/cdef...`
+        let path = cache_dir.entry_path(key);
+        assert_eq!(Some(cache_dir.as_path()), path.parent().and_then(Path::parent));
+        assert_eq!(2, path.parent().unwrap().file_name().unwrap().len());
+        assert_eq!(30, path.file_name().unwrap().len());
+    }
+
+    cache_dir.put("key", "replaced").unwrap();
+    assert_eq!(Some(b"replaced".to_vec()), cache_dir.get("key").unwrap());
+
+    for key in &keys {
+        assert!(cache_dir.remove(key).unwrap());
+        assert!(!cache_dir.remove(key).unwrap());
+        assert_eq!(None, cache_dir.get(key).unwrap());
+    }
+
+    // No temporary files were left behind
+    assert_eq!(0, cache_dir.size().unwrap());
+}
+
+#[test]
+fn entries_of_colliding_keys_are_not_returned() {
+    let app_cache_path = TestDir::new("entries_collision");
+
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_entries");
+
+    // Simulates a hash collision by moving the entry of another key into the file of `key`
+    cache_dir.put("other", b"value").unwrap();
+    let path = cache_dir.entry_path("key");
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::rename(cache_dir.entry_path("other"), &path).unwrap();
+
+    assert_eq!(None, cache_dir.get("key").unwrap());
+    assert!(!cache_dir.contains("key").unwrap());
+    assert!(!cache_dir.remove("key").unwrap());
+    assert!(path.is_file());
+
+    // A truncated entry file is a missing entry too
+    fs::write(&path, [3, 0]).unwrap();
+    assert_eq!(None, cache_dir.get("key").unwrap());
+
+    cache_dir.put("key", b"replaced").unwrap();
+    assert_eq!(Some(b"replaced".to_vec()), cache_dir.get("key").unwrap());
+}
+
+#[test]
+fn entries_are_never_torn() {
+    let app_cache_path = TestDir::new("entries_torn");
+
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_entries");
+
+    let writers: Vec<_> = (0..8u8).map(|value| {
+        let cache_dir = cache_dir.clone();
+        thread::spawn(move || {
+            for _ in 0..20 {
+                cache_dir.put("shared", vec![value; 64 * 1024]).unwrap();
+                let contents = cache_dir.get("shared").unwrap().unwrap();
+                assert_eq!(64 * 1024, contents.len());
+                assert!(contents.iter().all(|byte| *byte == contents[0]));
+            }
+        })
+    }).collect();
+
+    for writer in writers {
+        writer.join().unwrap();
+    }
+}
+
+#[test]
+fn write_atomic_stays_inside_the_cache_dir() {
+    let app_cache_path = TestDir::new("write_atomic");
+
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_write_atomic");
+    cache_dir.write_atomic("nested/dir/file", "first").unwrap();
+    cache_dir.write_atomic("nested/dir/file", "second").unwrap();
+    assert_eq!("second", fs::read_to_string(cache_dir.join("nested/dir/file")).unwrap());
+    assert_eq!(1, fs::read_dir(cache_dir.join("nested/dir")).unwrap().count());
+
+    for path in &["../outside", "", "nested/../../outside"] {
+        let err = cache_dir.write_atomic(path, "contents").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    }
+    let err = cache_dir.create_atomic(env::temp_dir().join("outside")).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+}
+
+#[test]
+fn atomic_files_are_moved_into_place_on_commit() {
+    use std::io::Write;
+
+    let app_cache_path = TestDir::new("create_atomic");
+
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_create_atomic");
+    let path = cache_dir.join("file");
+    cache_dir.write_atomic("file", "previous").unwrap();
+
+    let mut file = cache_dir.create_atomic("file").unwrap();
+    assert_eq!(path, file.path());
+    file.write_all(b"new ").unwrap();
+    file.write_all(b"contents").unwrap();
+    assert_eq!("previous", fs::read_to_string(&path).unwrap());
+    file.commit().unwrap();
+    assert_eq!("new contents", fs::read_to_string(&path).unwrap());
+
+    // Aborted and dropped files leave the previous file untouched
+    let mut file = cache_dir.create_atomic("file").unwrap();
+    file.write_all(b"aborted").unwrap();
+    file.abort().unwrap();
+
+    let mut file = cache_dir.create_atomic("file").unwrap();
+    file.write_all(b"dropped").unwrap();
+    drop(file);
+
+    assert_eq!("new contents", fs::read_to_string(&path).unwrap());
+    assert_eq!(1, fs::read_dir(&cache_dir).unwrap().count());
+}
+
+#[test]
+fn atomic_files_in_progress_are_not_cleaned_up() {
+    use std::io::Write;
+    use std::time::Duration;
+
+    let app_cache_path = TestDir::new("atomic_cleanup");
+
+    let cache_dir = CacheDirConfig::new("__cachedir_test_atomic_cleanup")
+                                   .app_cache_path(&app_cache_path)
+                                   .size_limit(0)
+                                   .get_cache_dir().unwrap();
+    let mut file = cache_dir.create_atomic("file").unwrap();
+    file.write_all(b"in progress").unwrap();
+
+    // Only the files named like the temporary files are skipped
+    fs::write(cache_dir.join(".file.not-a-pid.0.tmp"), b"other").unwrap();
+    assert_eq!(5, cache_dir.size().unwrap());
+    assert_eq!(1, cache_dir.prune(Duration::from_secs(0)).unwrap().files_removed());
+    assert_eq!(0, cache_dir.enforce_limit().unwrap().files_removed());
+
+    file.commit().unwrap();
+    assert_eq!("in progress", fs::read_to_string(cache_dir.join("file")).unwrap());
 }
 
 #[test]
 #[cfg(any(unix, windows))]
-fn create_tmp_cache() {
-    if cfg!(unix) {
-        let expected_cache_dir = PathBuf::from("/var/tmp/__cachedir_test_create_tmp_cache");
-        let cache_dir: PathBuf = CacheDirConfig::new("__cachedir_test_create_tmp_cache")
-                                                .tmp_cache(true)
-                                                .get_cache_dir().unwrap()
-                                                .into();
-        assert_eq!(expected_cache_dir, cache_dir);
-    } else {
-        let temp_dir = env::temp_dir();
-
-        if temp_dir.as_os_str().is_empty() {
-            // This test will fail in this case, so we will ignore it
-        } else {
-            let expected_cache_dir = temp_dir.join("__cachedir_test_create_tmp_cache");
-            let cache_dir: PathBuf = CacheDirConfig::new("__cachedir_test_create_tmp_cache")
-                                                    .tmp_cache(true)
-                                                    .get_cache_dir().unwrap()
-                                                    .into();
-            assert_eq!(expected_cache_dir, cache_dir);
+fn cache_dir_locks_exclude_each_other() {
+    let app_cache_path = TestDir::new("locks");
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_locks");
+
+    let exclusive = cache_dir.lock_exclusive().unwrap();
+    assert_eq!(LockMode::Exclusive, exclusive.mode());
+    assert!(exclusive.path().starts_with(&*cache_dir));
+    assert!(cache_dir.try_lock_exclusive().unwrap().is_none());
+    assert!(cache_dir.try_lock_shared().unwrap().is_none());
+    drop(exclusive);
+
+    let shared = cache_dir.lock_shared().unwrap();
+    let other_shared = cache_dir.try_lock_shared().unwrap();
+    assert!(other_shared.is_some());
+    assert!(cache_dir.try_lock_exclusive().unwrap().is_none());
+    drop(shared);
+    drop(other_shared);
+
+    // A blocking lock waits until the lock is released by the other thread
+    let exclusive = cache_dir.lock_exclusive().unwrap();
+    let waiter = {
+        let cache_dir = cache_dir.clone();
+        thread::spawn(move || cache_dir.lock_exclusive().map(|_| ()))
+    };
+    thread::sleep(std::time::Duration::from_millis(50));
+    assert!(!waiter.is_finished());
+    drop(exclusive);
+    waiter.join().unwrap().unwrap();
+}
+
+#[test]
+#[cfg(any(unix, windows))]
+fn entry_locks_are_independent() {
+    use std::time::Duration;
+
+    let app_cache_path = TestDir::new("entry_locks");
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_entry_locks");
+
+    let _dir_lock = cache_dir.lock_exclusive().unwrap();
+    let lock = cache_dir.lock_entry("first", LockMode::Exclusive).unwrap();
+    assert_eq!(cache_dir.entry_path("first").parent(), lock.path().parent());
+    assert!(cache_dir.try_lock_entry("first", LockMode::Shared).unwrap().is_none());
+    assert!(cache_dir.try_lock_entry("second", LockMode::Exclusive).unwrap().is_some());
+    drop(lock);
+    assert!(cache_dir.try_lock_entry("first", LockMode::Shared).unwrap().is_some());
+
+    // The lock files of the existing entries are not counted as cache contents, while
+    // the lock file of a missing entry is, so it can be cleaned up
+    let first_lock = cache_dir.lock_entry("first", LockMode::Shared).unwrap().path().to_owned();
+    let second_lock = cache_dir.lock_entry("second", LockMode::Shared).unwrap().path().to_owned();
+    cache_dir.put("first", b"value").unwrap();
+    fs::write(cache_dir.join(".other.lock"), b"not a lock").unwrap();
+    // The entry file stores the key length(8 bytes) and the key too
+    let entry_size = 8 + 5 + 5;
+    assert_eq!(entry_size + 10, cache_dir.size().unwrap());
+
+    // The entries and the lock files are removed together, unless they are locked
+    let held = cache_dir.lock_entry("first", LockMode::Shared).unwrap();
+    let report = cache_dir.prune(Duration::from_secs(0)).unwrap();
+    assert_eq!(2, report.files_removed());
+    assert!(report.removed().contains(&second_lock));
+    assert!(report.removed().contains(&cache_dir.join(".other.lock")));
+    assert_eq!(entry_size, cache_dir.size().unwrap());
+    assert!(first_lock.exists());
+    assert!(!second_lock.exists());
+
+    drop(held);
+    cache_dir.prune(Duration::from_secs(0)).unwrap();
+    assert_eq!(0, cache_dir.size().unwrap());
+    assert!(!first_lock.exists());
+    assert!(cache_dir.join(".cachedir.lock").exists());
+
+    // `remove` removes the lock file too, unless it is locked
+    cache_dir.put("first", b"value").unwrap();
+    let held = cache_dir.lock_entry("first", LockMode::Exclusive).unwrap();
+    assert!(cache_dir.remove("first").unwrap());
+    assert!(first_lock.exists());
+    drop(held);
+    cache_dir.put("first", b"value").unwrap();
+    drop(cache_dir.lock_entry("first", LockMode::Exclusive).unwrap());
+    assert!(cache_dir.remove("first").unwrap());
+    assert!(!first_lock.exists());
+}
+
+#[test]
+#[cfg(any(unix, windows))]
+fn entry_locks_survive_the_removal_of_their_lock_files() {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let app_cache_path = TestDir::new("removed_entry_locks");
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_removed_entry_locks");
+
+    // A thread waits for the lock, while the lock file is removed by the holder
+    let held = cache_dir.lock_entry("key", LockMode::Exclusive).unwrap();
+    let (sender, receiver) = mpsc::channel();
+    let waiter = {
+        let cache_dir = cache_dir.clone();
+        thread::spawn(move || {
+            let lock = cache_dir.lock_entry("key", LockMode::Exclusive).unwrap();
+            sender.send(()).unwrap();
+            thread::sleep(Duration::from_millis(200));
+            drop(lock);
+        })
+    };
+    thread::sleep(Duration::from_millis(100));
+    fs::remove_file(held.path()).unwrap();
+    drop(held);
+
+    // The waiter locked the new lock file, so the lock is still exclusive
+    receiver.recv().unwrap();
+    assert!(cache_dir.try_lock_entry("key", LockMode::Exclusive).unwrap().is_none());
+    waiter.join().unwrap();
+    assert!(cache_dir.try_lock_entry("key", LockMode::Exclusive).unwrap().is_some());
+}
+
+#[test]
+#[cfg(feature = "blob-store")]
+fn digests_are_parsed_from_hex() {
+    use cachedir::Digest;
+
+    let hex = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    assert_eq!(hex, Digest::from_hex(hex).unwrap().to_hex());
+    assert_eq!(Digest::from_hex(hex), Digest::from_hex(&hex.to_uppercase()));
+
+    assert_eq!(None, Digest::from_hex(&hex[2..]));
+    assert_eq!(None, Digest::from_hex(&format!("{}00", hex)));
+    assert_eq!(None, Digest::from_hex(&"+f".repeat(32)));
+    assert_eq!(None, Digest::from_hex(&format!("-1{}", &hex[2..])));
+    assert_eq!(None, Digest::from_hex(&format!("g0{}", &hex[2..])));
+}
+
+#[test]
+#[cfg(feature = "blob-store")]
+fn blobs_are_content_addressed_and_deduplicated() {
+    use std::io::Read;
+
+    let app_cache_path = TestDir::new("blobs");
+
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_blobs");
+
+    // The SHA-256 hash of "hello"
+    let hex = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    let digest = cache_dir.store_blob(&b"hello"[..]).unwrap();
+    assert_eq!(hex, digest.to_hex());
+    assert_eq!(Some(digest), cachedir::Digest::from_hex(&hex.to_uppercase()));
+    assert_eq!(cache_dir.join("blobs/sha256/2c").join(hex), cache_dir.blob_path(&digest));
+
+    // Storing the same contents again reuses the blob and leaves no temporary files behind
+    assert_eq!(digest, cache_dir.store_blob(&b"hello"[..]).unwrap());
+    let algorithm_dir = cache_dir.join("blobs/sha256");
+    assert_eq!(vec![algorithm_dir.join("2c")],
+               fs::read_dir(&algorithm_dir).unwrap().map(|entry| entry.unwrap().path())
+                                                    .collect::<Vec<_>>());
+
+    // Larger than the buffer used for streaming
+    let large: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+    let large_digest = cache_dir.store_blob(&large[..]).unwrap();
+    assert!(large_digest != digest);
+
+    let mut contents = Vec::new();
+    cache_dir.open_blob(&large_digest).unwrap().read_to_end(&mut contents).unwrap();
+    assert_eq!(large, contents);
+
+    let missing = cachedir::Digest::from_bytes([0; 32]);
+    assert_eq!(io::ErrorKind::NotFound, cache_dir.open_blob(&missing).unwrap_err().kind());
+}
+
+#[test]
+#[cfg(feature = "blob-store")]
+fn corrupted_blobs_fail_to_read() {
+    use std::io::Read;
+
+    let app_cache_path = TestDir::new("corrupted_blobs");
+
+    let cache_dir = app_cache_path.app_cache("__cachedir_test_corrupted_blobs");
+
+    let digest = cache_dir.store_blob(&b"original contents"[..]).unwrap();
+    fs::write(cache_dir.blob_path(&digest), b"modified contents").unwrap();
+
+    let mut contents = Vec::new();
+    let err = cache_dir.open_blob(&digest).unwrap().read_to_end(&mut contents).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidData, err.kind());
+}
+
+#[test]
+fn fs_type_is_ram_backed() {
+    assert!(FsType::Tmpfs.is_ram_backed());
+    assert!(FsType::Ramfs.is_ram_backed());
+    assert!(!FsType::Other(0xEF53).is_ram_backed());
+    assert!(!FsType::Unknown.is_ram_backed());
+    assert_eq!("file system 0xef53", FsType::Other(0xEF53).to_string());
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn xdg_runtime_dir_must_be_absolute() {
+    let mut environment = fake_env();
+    environment.set_var("XDG_RUNTIME_DIR", "relative/runtime");
+
+    let candidates = CacheDirConfig::new("__cachedir_test_runtime_cache")
+                                    .environment(&environment)
+                                    .mem_cache(true)
+                                    .candidates().unwrap();
+    assert_eq!(CandidateStatus::Invalid, candidates[0].status());
+    assert_eq!(Path::new("relative/runtime"), candidates[0].path());
+    assert!(candidates[0].reason().unwrap().contains("XDG_RUNTIME_DIR"));
+    assert!(candidates[1..].iter().all(|candidate| candidate.path().is_absolute()));
+
+    let result = CacheDirConfig::new("__cachedir_test_runtime_cache")
+                                .environment(&environment)
+                                .mem_cache(true)
+                                .get_cache_dir();
+    match result {
+        // The cache was created in `/dev/shm` or `/run/shm`
+        Ok(cache_dir) => assert!(cache_dir.is_absolute()),
+        Err(err)      => {
+            let attempt = &err.attempts()[0];
+            assert_eq!(CacheKind::Memory, attempt.kind());
+            assert_eq!(Some(Path::new("relative/runtime")), attempt.path());
+            assert_eq!(io::ErrorKind::InvalidInput, attempt.error().kind());
         }
     }
 }
 
 #[test]
-#[cfg(target_os = "linux")]
-fn create_mem_cache() {
-    use std::path::Path;
+fn cachedir_tag_is_written_when_requested() {
+    let app_cache_path = TestDir::new("cachedir_tag");
 
-    let dev_shm = Path::new("/dev/shm");
-    let run_shm = Path::new("/run/shm");
+    let untagged = app_cache_path.app_cache("untagged");
+    assert!(!untagged.is_tagged());
+    assert!(!untagged.join("CACHEDIR.TAG").exists());
 
-    let expected_cache_dir: Option<PathBuf> = if dev_shm.is_dir() {
-        Some(PathBuf::from(dev_shm))
-    } else if run_shm.is_dir() {
-        Some(PathBuf::from(run_shm))
-    } else {
-        None
+    let tagged = CacheDirConfig::new("tagged")
+                                .app_cache_path(app_cache_path.as_path())
+                                .cachedir_tag(true)
+                                .get_cache_dir().unwrap();
+    assert!(tagged.is_tagged());
+
+    let contents = fs::read_to_string(tagged.join("CACHEDIR.TAG")).unwrap();
+    assert!(contents.starts_with("Signature: 8a477f597d28d172789f06886806bc55"));
+    assert_eq!(1, fs::read_dir(tagged.as_path()).unwrap().count());
+}
+
+#[test]
+fn cachedir_tag_keeps_valid_and_replaces_invalid_tags() {
+    let app_cache_path = TestDir::new("cachedir_tag_existing");
+
+    let valid_tag = "Signature: 8a477f597d28d172789f06886806bc55\n# Custom comment\n";
+    let valid_dir = app_cache_path.join("valid");
+    fs::create_dir_all(&valid_dir).unwrap();
+    fs::write(valid_dir.join("CACHEDIR.TAG"), valid_tag).unwrap();
+
+    let cache_dir = CacheDirConfig::new("valid")
+                                   .app_cache_path(app_cache_path.as_path())
+                                   .cachedir_tag(true)
+                                   .get_cache_dir().unwrap();
+    assert_eq!(valid_tag, fs::read_to_string(cache_dir.join("CACHEDIR.TAG")).unwrap());
+
+    let invalid_dir = app_cache_path.join("invalid");
+    fs::create_dir_all(&invalid_dir).unwrap();
+    fs::write(invalid_dir.join("CACHEDIR.TAG"), "Not a signature").unwrap();
+
+    let cache_dir = app_cache_path.app_cache("invalid");
+    assert!(!cache_dir.is_tagged());
+
+    let cache_dir = CacheDirConfig::new("invalid")
+                                   .app_cache_path(app_cache_path.as_path())
+                                   .cachedir_tag(true)
+                                   .get_cache_dir().unwrap();
+    assert!(cache_dir.is_tagged());
+}
+
+#[test]
+fn cachedir_tag_can_be_written_by_many_threads() {
+    let app_cache_path = TestDir::new("cachedir_tag_threads");
+
+    let threads: Vec<_> = (0..8).map(|_| {
+        let app_cache_path = app_cache_path.clone();
+        thread::spawn(move || {
+            CacheDirConfig::new("tagged")
+                           .app_cache_path(app_cache_path.as_path())
+                           .cachedir_tag(true)
+                           .get_cache_dir()
+        })
+    }).collect();
+
+    for thread in threads {
+        assert!(thread.join().unwrap().unwrap().is_tagged());
+    }
+
+    // No temporary file is left behind
+    let tagged = app_cache_path.join("tagged");
+    assert_eq!(1, fs::read_dir(&tagged).unwrap().count());
+}
+
+#[test]
+fn cachedir_tag_failures_fall_through_to_the_next_location() {
+    let app_cache_path = TestDir::new("cachedir_tag_failure");
+    let home_dir = TestDir::new("cachedir_tag_failure_home");
+
+    // The tag can't be written over a directory
+    fs::create_dir_all(app_cache_path.join("tagged").join("CACHEDIR.TAG")).unwrap();
+
+    let mut environment = fake_env();
+    environment.set_home_dir(&home_dir)
+               .set_var("LOCALAPPDATA", &home_dir);
+    let cache_dir = CacheDirConfig::new("tagged")
+                                   .app_cache_path(app_cache_path.as_path())
+                                   .user_cache(true)
+                                   .cachedir_tag(true)
+                                   .environment(&environment)
+                                   .get_cache_dir().unwrap();
+    assert_eq!(CacheKind::User, cache_dir.kind());
+    assert!(cache_dir.is_tagged());
+
+    let err = CacheDirConfig::new("tagged")
+                             .app_cache_path(app_cache_path.as_path())
+                             .cachedir_tag(true)
+                             .get_cache_dir().unwrap_err();
+    assert_eq!(CacheKind::App, err.attempts()[0].kind());
+    assert!(err.attempts()[0].error().to_string().contains("CACHEDIR.TAG"));
+}
+
+#[test]
+fn cache_dir_error_records_every_attempt() {
+    let not_a_dir = TestDir::new("error_not_a_dir");
+    fs::write(&not_a_dir, "").unwrap();
+
+    let err = CacheDirConfig::new("example")
+                             .app_cache_path(not_a_dir.join("app").as_path())
+                             .get_cache_dir().unwrap_err();
+
+    assert_eq!(1, err.attempts().len());
+    let attempt = &err.attempts()[0];
+    assert_eq!(CacheKind::App, attempt.kind());
+    assert_eq!(Some(not_a_dir.join("app").as_path()), attempt.path());
+    assert_eq!(attempt.error().kind(), err.kind());
+    assert!(err.source().is_some());
+    assert!(err.to_string().contains("[Application Cache]"));
+
+    let io_err: io::Error = err.into();
+    assert!(io_err.get_ref().unwrap().downcast_ref::<CacheDirError>().is_some());
+}
+
+#[test]
+#[cfg(all(unix, not(target_os = "macos")))]
+fn cache_dir_error_keeps_attempts_in_order() {
+    let not_a_dir = TestDir::new("error_order_not_a_dir");
+    fs::write(&not_a_dir, "").unwrap();
+
+    // `app_cache_path` is absolute, so it does not depend on the current directory
+    let mut environment = fake_env();
+    environment.set_var("XDG_CACHE_HOME", "relative/cache").set_current_dir("/");
+
+    let err = CacheDirConfig::new("example")
+                             .environment(&environment)
+                             .app_cache_path(not_a_dir.join("app").as_path())
+                             .user_cache(true)
+                             .get_cache_dir().unwrap_err();
+
+    let kinds: Vec<CacheKind> = err.attempts().iter().map(|attempt| attempt.kind()).collect();
+    assert_eq!(vec![CacheKind::App, CacheKind::User], kinds);
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    assert_eq!(Some(not_a_dir.join("app").as_path()), err.attempts()[0].path());
+    assert_eq!(Some(Path::new("relative/cache")), err.attempts()[1].path());
+}
+
+#[test]
+fn cache_dir_reports_selected_kind() {
+    let app_cache_path = TestDir::new("selected_kind");
+    let cache_dir = CacheDirConfig::new("example")
+                                   .app_cache_path(app_cache_path.as_path())
+                                   .try_all_caches()
+                                   .get_cache_dir().unwrap();
+    assert_eq!(CacheKind::App, cache_dir.kind());
+    assert_eq!(Persistence::Persistent, cache_dir.persistence());
+
+    let not_a_dir = TestDir::new("selected_kind_not_a_dir");
+    fs::write(&not_a_dir, "").unwrap();
+
+    // The application cache fails, so the tmp cache is selected
+    let mut cleanup = RemoveOnDrop::new();
+    let cache_dir = CacheDirConfig::new(&unique_name("selected_kind"))
+                                   .app_cache_path(not_a_dir.join("app").as_path())
+                                   .tmp_cache(true)
+                                   .get_cache_dir().unwrap();
+    cleanup.push(&cache_dir);
+    assert_eq!(CacheKind::Tmp, cache_dir.kind());
+    assert_eq!(Persistence::Temporary, cache_dir.persistence());
+    assert!(cache_dir.persistence().survives_reboot());
+}
+
+#[test]
+fn cache_dirs_are_compared_by_path() {
+    use std::borrow::Borrow;
+    use std::collections::{ BTreeSet, HashSet };
+
+    let app_cache_path = TestDir::new("compared_by_path");
+    let get_cache_dir = |min_free_space: u64| {
+        CacheDirConfig::new("example")
+                       .app_cache_path(app_cache_path.as_path())
+                       .min_free_space(min_free_space)
+                       .get_cache_dir().unwrap()
     };
 
-    if expected_cache_dir.is_none() {
-        // This test will fail in this case, so we will ignore it
-    } else {
-        let expected_cache_dir = expected_cache_dir.unwrap()
-                                                   .join("__cachedir_test_create_mem_cache");
-        let cache_dir: PathBuf = CacheDirConfig::new("__cachedir_test_create_mem_cache")
-                                                .mem_cache(true)
-                                                .get_cache_dir().unwrap()
-                                                .into();
-        assert_eq!(expected_cache_dir, cache_dir);
+    // The same directory, obtained with different configurations
+    let cache_dir = get_cache_dir(0);
+    assert_eq!(cache_dir, get_cache_dir(1));
+
+    let path = app_cache_path.join("example");
+    let os_str: &OsStr = cache_dir.borrow();
+    assert_eq!(path.as_os_str(), os_str);
+
+    let hash_set: HashSet<_> = vec![cache_dir.clone()].into_iter().collect();
+    assert!(hash_set.contains(path.as_path()));
+    assert!(hash_set.contains(&path));
+
+    let btree_set: BTreeSet<_> = vec![cache_dir].into_iter().collect();
+    assert!(btree_set.contains(path.as_path()));
+    assert!(!btree_set.contains(app_cache_path.as_path()));
+}
+
+#[test]
+fn order_overrides_default_priority() {
+    let app_cache_path = TestDir::new("order");
+    let cache_name = unique_name("order");
+    let mut cleanup = RemoveOnDrop::new();
+
+    // The application cache would be attempted first by default
+    let cache_dir = CacheDirConfig::new(&cache_name)
+                                   .app_cache_path(app_cache_path.as_path())
+                                   .tmp_cache(true)
+                                   .order(&[CacheKind::Tmp, CacheKind::App])
+                                   .get_cache_dir().unwrap();
+    cleanup.push(&cache_dir);
+    assert_eq!(CacheKind::Tmp, cache_dir.kind());
+
+    // `order` enables the cache options it was given
+    let cache_dir = CacheDirConfig::new(&cache_name)
+                                   .order(&[CacheKind::Tmp])
+                                   .get_cache_dir().unwrap();
+    assert_eq!(CacheKind::Tmp, cache_dir.kind());
+
+    // Enabled cache options missing from `order` are attempted afterwards in the default order
+    let cache_dir = CacheDirConfig::new(&cache_name)
+                                   .app_cache_path(app_cache_path.as_path())
+                                   .try_all_caches()
+                                   .order(&[CacheKind::Tmp])
+                                   .get_cache_dir().unwrap();
+    assert_eq!(CacheKind::Tmp, cache_dir.kind());
+}
+
+#[test]
+fn order_is_followed_when_falling_back() {
+    let not_a_dir = TestDir::new("order_not_a_dir");
+    fs::write(&not_a_dir, "").unwrap();
+
+    let err = CacheDirConfig::new("__cachedir_test_order")
+                             .app_cache_path(not_a_dir.join("first").as_path())
+                             .order(&[CacheKind::App])
+                             .get_cache_dir().unwrap_err();
+    assert_eq!(vec![CacheKind::App],
+               err.attempts().iter().map(|attempt| attempt.kind()).collect::<Vec<_>>());
+
+    let mut cleanup = RemoveOnDrop::new();
+    let cache_dir = CacheDirConfig::new(&unique_name("order_fallback"))
+                                   .app_cache_path(not_a_dir.join("first").as_path())
+                                   .order(&[CacheKind::App, CacheKind::Tmp])
+                                   .get_cache_dir().unwrap();
+    cleanup.push(&cache_dir);
+    assert_eq!(CacheKind::Tmp, cache_dir.kind());
+}
+
+#[test]
+fn candidates_do_not_touch_the_filesystem() {
+    // The entries and the modification time of a directory: a file that was created and
+    // removed(ex: a probe file) changes the modification time
+    let snapshot = |path: &Path| {
+        let mut entries: Vec<PathBuf> = fs::read_dir(path).unwrap()
+                                                          .map(|entry| entry.unwrap().path())
+                                                          .collect();
+        entries.sort();
+        (entries, fs::metadata(path).unwrap().modified().unwrap())
+    };
+
+    // The nearest existing ancestor of the application cache belongs to the test
+    let test_dir = TestDir::new("candidates");
+    fs::create_dir_all(&test_dir).unwrap();
+    let app_cache_path = test_dir.join("app");
+
+    let mut cache_config = CacheDirConfig::new("example");
+    cache_config.app_cache_path(app_cache_path.as_path())
+                .tmp_cache(true);
+
+    let before = snapshot(&test_dir);
+    let candidates = cache_config.candidates().unwrap();
+    assert_eq!(before, snapshot(&test_dir));
+    assert!(!app_cache_path.exists());
+
+    let kinds: Vec<CacheKind> = candidates.iter().map(|candidate| candidate.kind()).collect();
+    assert_eq!(CacheKind::App, kinds[0]);
+    assert!(kinds[1..].iter().all(|kind| *kind == CacheKind::Tmp));
+
+    assert_eq!(app_cache_path.join("example"), candidates[0].path());
+    assert_eq!(CandidateStatus::Creatable, candidates[0].status());
+    assert!(candidates[0].status().is_usable());
+
+    // The first candidate is the one that `get_cache_dir` selects
+    let cache_dir = cache_config.get_cache_dir().unwrap();
+    assert_eq!(candidates[0].path(), cache_dir.as_path());
+
+    let before = snapshot(&cache_dir);
+    assert_eq!(CandidateStatus::Exists, cache_config.candidates().unwrap()[0].status());
+    assert_eq!(before, snapshot(&cache_dir));
+}
+
+#[test]
+fn candidates_report_paths_that_are_not_directories() {
+    let app_cache_path = TestDir::new("candidates_not_a_dir");
+    fs::create_dir_all(&app_cache_path).unwrap();
+    fs::write(app_cache_path.join("file"), "").unwrap();
+
+    let candidates = CacheDirConfig::new("file")
+                                    .app_cache_path(app_cache_path.as_path())
+                                    .candidates().unwrap();
+    assert_eq!(1, candidates.len());
+    assert_eq!(CandidateStatus::NotADirectory, candidates[0].status());
+
+    let candidates = CacheDirConfig::new("file/nested")
+                                    .nested_cache_name(true)
+                                    .app_cache_path(app_cache_path.as_path())
+                                    .candidates().unwrap();
+    assert_eq!(CandidateStatus::NotADirectory, candidates[0].status());
+    assert!(!candidates[0].status().is_usable());
+}
+
+#[test]
+fn cache_dir_config_is_owned() {
+    fn assert_owned<T: Clone + Send + Sync + fmt::Debug + 'static>(value: T) -> T { value }
+
+    let test_dir = TestDir::new("owned_config");
+    let cache_config = {
+        let cache_name = String::from("__cachedir_test_owned_config");
+        let app_cache_path = test_dir.to_path_buf();
+        let mut cache_config = CacheDirConfig::new(&cache_name);
+        cache_config.app_cache_path(&app_cache_path);
+        assert_owned(cache_config)
+    };
+
+    let cloned = cache_config.clone();
+    let cache_dir = thread::spawn(move || cloned.get_cache_dir().unwrap()).join().unwrap();
+    assert_eq!(cache_config.get_cache_dir().unwrap(), cache_dir);
+    assert!(format!("{:?}", cache_config).contains("__cachedir_test_owned_config"));
+}
+
+#[test]
+fn cache_name_is_validated() {
+    let app_cache_path = TestDir::new("cache_name");
+
+    let cache_name_error = |cache_name: &str| {
+        CacheDirConfig::new(cache_name)
+                       .app_cache_path(app_cache_path.as_path())
+                       .get_cache_dir().err()
+                       .and_then(|err| err.invalid_cache_name().cloned())
+    };
+
+    assert_eq!(Some(CacheNameError::Empty), cache_name_error(""));
+    assert_eq!(Some(CacheNameError::Empty), cache_name_error("."));
+    assert_eq!(Some(CacheNameError::ParentDir), cache_name_error(".."));
+    assert_eq!(Some(CacheNameError::ParentDir), cache_name_error("../../etc"));
+    assert_eq!(Some(CacheNameError::Absolute), cache_name_error("/tmp/x"));
+    assert_eq!(Some(CacheNameError::Reserved(String::from("con"))), cache_name_error("con"));
+    assert_eq!(Some(CacheNameError::Reserved(String::from("NUL.txt"))),
+               cache_name_error("NUL.txt"));
+    for reserved in &["COM0", "lpt0", "CONIN$", "conout$.log"] {
+        assert_eq!(Some(CacheNameError::Reserved(reserved.to_string())),
+                   cache_name_error(reserved));
+    }
+    assert_eq!(Some(CacheNameError::Nested), cache_name_error("org/app"));
+
+    // Similar, but valid names
+    assert_eq!(None, cache_name_error("console"));
+    assert_eq!(None, cache_name_error("COM10"));
+    assert_eq!(None, cache_name_error("..cache"));
+    assert_eq!(None, cache_name_error("./cache"));
+}
+
+#[test]
+fn cache_name_errors_are_reported_before_any_attempt() {
+    let err = CacheDirConfig::new("../escape")
+                             .try_all_caches()
+                             .get_cache_dir().unwrap_err();
+    assert!(err.attempts().is_empty());
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    assert!(err.source().is_some());
+
+    assert_eq!(CacheNameError::ParentDir,
+               CacheDirConfig::new("../escape").candidates().unwrap_err());
+
+    // Nested names still can not escape the parent directory
+    assert_eq!(Some(&CacheNameError::ParentDir),
+               CacheDirConfig::new("org/../../escape")
+                              .nested_cache_name(true)
+                              .get_cache_dir().unwrap_err()
+                              .invalid_cache_name());
+}
+
+#[test]
+fn app_identity_names_follow_the_target_os() {
+    // The literal names are compared, so they use the separator of the target OS on every host
+    let cache_name = |identity: &AppIdentity, target_os: TargetOs| {
+        identity.cache_name(target_os).to_str().unwrap().to_owned()
+    };
+
+    let identity = AppIdentity::new("com", "Example Corp", "Build/Tool");
+    assert_eq!("com.Example-Corp.Build-Tool", cache_name(&identity, TargetOs::MacOs));
+    assert_eq!(r"Example Corp\Build-Tool\Cache", cache_name(&identity, TargetOs::Windows));
+    for &target_os in &[TargetOs::Linux, TargetOs::Redox, TargetOs::Emscripten,
+                        TargetOs::OtherUnix, TargetOs::Unknown] {
+        assert_eq!("example-corp/build-tool", cache_name(&identity, target_os));
+    }
+
+    // The empty parts are skipped
+    let identity = AppIdentity::new("", "", "App");
+    assert_eq!("App", cache_name(&identity, TargetOs::MacOs));
+    assert_eq!(r"App\Cache", cache_name(&identity, TargetOs::Windows));
+    for &target_os in &[TargetOs::Linux, TargetOs::Redox, TargetOs::Emscripten,
+                        TargetOs::OtherUnix, TargetOs::Unknown] {
+        assert_eq!("app", cache_name(&identity, target_os));
+    }
+}
+
+#[test]
+fn cache_dir_config_from_app_identity() {
+    let app_cache_path = TestDir::new("app_identity");
+
+    let identity = AppIdentity::new("org", "Cachedir", "Test App");
+    let cache_dir = CacheDirConfig::from_app_identity(&identity)
+                                   .app_cache_path(&app_cache_path)
+                                   .get_cache_dir().unwrap();
+    assert_eq!(app_cache_path.join(identity.cache_name(TargetOs::current())),
+               cache_dir.into_path_buf());
+
+    // The names derived from an identity are validated like any other cache name
+    assert_eq!(Some(&CacheNameError::ParentDir),
+               CacheDirConfig::from_app_identity(&AppIdentity::new("", "..", ".."))
+                              .app_cache_path(&app_cache_path)
+                              .get_cache_dir().unwrap_err()
+                              .invalid_cache_name());
+}
+
+// Collects the kinds and paths of the locations of `identity` that would be attempted
+// on `target_os`
+fn locations(identity:    &AppIdentity,
+             environment: &CustomEnvironment,
+             target_os:   TargetOs) -> Vec<(CacheKind, PathBuf)> {
+    CacheDirConfig::new(&identity.cache_name(target_os))
+                   .nested_cache_name(true)
+                   .user_cache(true)
+                   .sys_cache(true)
+                   .tmp_cache(true)
+                   .mem_cache(true)
+                   .environment(environment)
+                   .locations(target_os).unwrap()
+                   .iter()
+                   .filter(|location| location.status().is_none())
+                   .map(|location| (location.kind(), location.path().to_path_buf()))
+                   .collect()
+}
+
+#[test]
+fn locations_follow_the_target_os() {
+    let identity = AppIdentity::new("com", "Example", "App");
+    let mut environment = CustomEnvironment::new();
+    environment.set_home_dir("/home/me")
+               .set_temp_dir("/tmp")
+               .set_uid(1000);
+
+    // The per-user segment is added only inside of the shared directories
+    assert_eq!(vec![(CacheKind::User,   PathBuf::from("/home/me/.cache/example/app")),
+                    (CacheKind::System, PathBuf::from("/var/cache/example/app")),
+                    (CacheKind::Tmp,    PathBuf::from("/var/tmp/1000/example/app")),
+                    (CacheKind::Tmp,    PathBuf::from("/tmp/1000/example/app")),
+                    (CacheKind::Memory, PathBuf::from("/dev/shm/1000/example/app")),
+                    (CacheKind::Memory, PathBuf::from("/run/shm/1000/example/app"))],
+               locations(&identity, &environment, TargetOs::Linux));
+
+    // There are no memory caches on macOS
+    assert_eq!(vec![(CacheKind::User,   PathBuf::from("/home/me/Library/Caches/com.Example.App")),
+                    (CacheKind::System, PathBuf::from("/Library/Caches/com.Example.App")),
+                    (CacheKind::Tmp,    PathBuf::from("/var/tmp/1000/com.Example.App")),
+                    (CacheKind::Tmp,    PathBuf::from("/tmp/1000/com.Example.App"))],
+               locations(&identity, &environment, TargetOs::MacOs));
+
+    // `$XDG_CACHE_HOME` and `$XDG_RUNTIME_DIR` are used only where the XDG specification is
+    environment.set_var("XDG_CACHE_HOME", "/xdg/cache")
+               .set_var("XDG_RUNTIME_DIR", "/run/user/1000");
+    assert_eq!((CacheKind::User, PathBuf::from("/xdg/cache/example/app")),
+               locations(&identity, &environment, TargetOs::Linux)[0]);
+    assert_eq!((CacheKind::Memory, PathBuf::from("/run/user/1000/example/app")),
+               locations(&identity, &environment, TargetOs::Linux)[4]);
+    assert_eq!((CacheKind::User, PathBuf::from("/home/me/Library/Caches/com.Example.App")),
+               locations(&identity, &environment, TargetOs::MacOs)[0]);
+}
+
+#[test]
+fn home_dir_sources_follow_the_target_os() {
+    let user_cache = |environment: &CustomEnvironment, target_os: TargetOs| {
+        CacheDirConfig::new("app")
+                       .environment(environment)
+                       .locations(target_os).unwrap()
+                       .iter()
+                       .find(|location| location.status().is_none())
+                       .map(|location| location.path().as_os_str().to_os_string())
+    };
+
+    // `$HOME` takes precedence over the passwd entry, unless it is empty
+    let mut unix = CustomEnvironment::new();
+    unix.set_home_dir("/passwd/home");
+    assert_eq!(Some(OsStr::new("/passwd/home/.cache/app").to_os_string()),
+               user_cache(&unix, TargetOs::Linux));
+    unix.set_var("HOME", "");
+    assert_eq!(Some(OsStr::new("/passwd/home/.cache/app").to_os_string()),
+               user_cache(&unix, TargetOs::Linux));
+    unix.set_var("HOME", "/env/home");
+    assert_eq!(Some(OsStr::new("/env/home/.cache/app").to_os_string()),
+               user_cache(&unix, TargetOs::Linux));
+    assert_eq!(Some(OsStr::new("/env/home/.cache/app").to_os_string()),
+               user_cache(&unix, TargetOs::Redox));
+
+    // The `Profile` known folder takes precedence over `%USERPROFILE%` and `%HOME%` is ignored
+    let mut windows = CustomEnvironment::new();
+    windows.set_var("HOME", r"C:\home");
+    assert_eq!(None, user_cache(&windows, TargetOs::Windows));
+    let unavailable = &CacheDirConfig::new("app")
+                                      .environment(&windows)
+                                      .locations(TargetOs::Windows).unwrap()[1];
+    assert_eq!(Some(CandidateStatus::Unavailable), unavailable.status());
+    assert_eq!(Path::new(""), unavailable.path());
+    assert!(unavailable.reason().unwrap().contains("%USERPROFILE% is not set"));
+    windows.set_var("USERPROFILE", r"C:\Users\env");
+    assert_eq!(Some(OsStr::new(r"C:\Users\env\Cache\app").to_os_string()),
+               user_cache(&windows, TargetOs::Windows));
+    windows.set_home_dir(r"C:\Users\profile");
+    assert_eq!(Some(OsStr::new(r"C:\Users\profile\Cache\app").to_os_string()),
+               user_cache(&windows, TargetOs::Windows));
+}
+
+#[test]
+fn process_home_dir_is_absolute() {
+    // The home directory of the process comes from the OS, not from `$HOME`
+    if let Some(home_dir) = ProcessEnvironment.home_dir() {
+        assert!(home_dir.is_absolute());
+    }
+}
+
+#[test]
+fn windows_locations_are_computed_on_any_os() {
+    let identity = AppIdentity::new("com", "Example", "App");
+    let mut environment = CustomEnvironment::new();
+    environment.set_var("LOCALAPPDATA", r"C:\Users\me\AppData\Local")
+               .set_var("ProgramData", r"C:\ProgramData")
+               .set_temp_dir(r"C:\Users\me\AppData\Local\Temp");
+
+    let locations = locations(&identity, &environment, TargetOs::Windows);
+    let paths: Vec<&OsStr> = locations.iter().map(|(_, path)| path.as_os_str()).collect();
+    assert_eq!(vec![OsStr::new(r"C:\Users\me\AppData\Local\Example\App\Cache"),
+                    OsStr::new(r"C:\ProgramData\Example\App\Cache"),
+                    OsStr::new(r"C:\Users\me\AppData\Local\Temp\Example\App\Cache")],
+               paths);
+}
+
+#[test]
+fn windows_per_user_directories_use_the_user_sid() {
+    let system_location = |environment: &CustomEnvironment| {
+        let locations = CacheDirConfig::new("app")
+                                       .sys_cache(true)
+                                       .per_user(CacheKind::System, true)
+                                       .environment(environment)
+                                       .locations(TargetOs::Windows).unwrap();
+        (locations[0].status(), locations[0].path().as_os_str().to_os_string())
+    };
+
+    // `%USERNAME%` can be spoofed, so it is never used
+    let mut environment = CustomEnvironment::new();
+    environment.set_known_folder(KnownFolder::ProgramData, r"C:\ProgramData")
+               .set_var("USERNAME", "me");
+    assert_eq!(Some(CandidateStatus::Unavailable), system_location(&environment).0);
+
+    environment.set_user_sid("S-1-5-21-1004336348-1177238915-682003330-1001");
+    let expected = OsStr::new(r"C:\ProgramData\S-1-5-21-1004336348-1177238915-682003330-1001\app");
+    assert_eq!((None, expected.to_os_string()), system_location(&environment));
+}
+
+#[test]
+fn windows_known_folders_take_precedence_over_the_environment() {
+    let windows_locations = |environment: &CustomEnvironment| -> Vec<(CacheKind, PathBuf)> {
+        CacheDirConfig::new("app")
+                       .user_cache(true)
+                       .sys_cache(true)
+                       .environment(environment)
+                       .locations(TargetOs::Windows).unwrap()
+                       .iter()
+                       .filter(|location| location.status().is_none())
+                       .map(|location| (location.kind(), location.path().to_path_buf()))
+                       .collect()
+    };
+
+    // The roaming `%APPDATA%` is never used
+    let mut environment = CustomEnvironment::new();
+    environment.set_var("APPDATA", r"C:\Users\me\AppData\Roaming");
+    assert!(windows_locations(&environment).is_empty());
+
+    // The environment variables are only a fallback
+    environment.set_var("LOCALAPPDATA", r"C:\spoofed\local")
+               .set_var("ProgramData", r"C:\spoofed\data");
+    assert_eq!(vec![(CacheKind::User,   PathBuf::from(r"C:\spoofed\local\app")),
+                    (CacheKind::System, PathBuf::from(r"C:\spoofed\data\app"))],
+               windows_locations(&environment));
+
+    environment.set_known_folder(KnownFolder::LocalAppData, r"C:\Users\me\AppData\Local")
+               .set_known_folder(KnownFolder::ProgramData, r"C:\ProgramData");
+    let locations = windows_locations(&environment);
+    let paths: Vec<&OsStr> = locations.iter().map(|(_, path)| path.as_os_str()).collect();
+    assert_eq!(vec![OsStr::new(r"C:\Users\me\AppData\Local\app"),
+                    OsStr::new(r"C:\ProgramData\app")],
+               paths);
+}
+
+#[test]
+fn relative_locations_are_checked_for_the_target_os() {
+    let mut environment = CustomEnvironment::new();
+    environment.set_home_dir("/home/me")
+               .set_var("XDG_CACHE_HOME", "relative/cache");
+
+    // `$XDG_CACHE_HOME` must be absolute, so the user cache is rejected on Linux
+    let user_cache_status = |environment: &CustomEnvironment| {
+        CacheDirConfig::new("app")
+                       .user_cache(true)
+                       .environment(environment)
+                       .locations(TargetOs::Linux).unwrap()
+                       .iter()
+                       .map(|location| location.status())
+                       .collect::<Vec<_>>()
+    };
+    assert_eq!(vec![Some(CandidateStatus::Invalid)], user_cache_status(&environment));
+
+    // A Windows path is relative on Linux
+    environment.set_var("XDG_CACHE_HOME", r"C:\cache");
+    assert_eq!(vec![Some(CandidateStatus::Invalid)], user_cache_status(&environment));
+    environment.set_var("XDG_CACHE_HOME", "/cache");
+    assert_eq!(vec![None], user_cache_status(&environment));
+
+    // The app cache path is joined onto the current directory only if it is relative on Windows
+    let mut windows = CustomEnvironment::new();
+    windows.set_current_dir(r"D:\current");
+    let app_cache_location = |app_cache_path: &str| {
+        CacheDirConfig::new("app")
+                       .app_cache_path(app_cache_path)
+                       .app_cache(true)
+                       .environment(&windows)
+                       .locations(TargetOs::Windows).unwrap()[0]
+                       .path().as_os_str().to_os_string()
+    };
+    assert_eq!(OsStr::new(r"C:\app\cache\app"), app_cache_location(r"C:\app\cache"));
+    assert_eq!(OsStr::new(r"D:\current\cache\app"), app_cache_location("cache"));
+
+    // The names are validated before computing anything
+    assert_eq!(CacheNameError::ParentDir,
+               CacheDirConfig::new("..").locations(TargetOs::Windows).unwrap_err());
+}
+
+#[test]
+fn default_app_cache_follows_the_target_os() {
+    let mut environment = CustomEnvironment::new();
+    environment.set_current_dir("/current");
+    let app_cache_location = |environment: &CustomEnvironment, target_os: TargetOs| {
+        CacheDirConfig::new("app")
+                       .app_cache(true)
+                       .environment(environment)
+                       .locations(target_os).unwrap()[0]
+                       .path().as_os_str().to_os_string()
+    };
+
+    for &target_os in &[TargetOs::Linux, TargetOs::MacOs, TargetOs::Redox] {
+        assert_eq!(OsStr::new("/current/.cache/app"), app_cache_location(&environment, target_os));
+    }
+
+    environment.set_current_dir(r"D:\current");
+    assert_eq!(OsStr::new(r"D:\current\Cache\app"),
+               app_cache_location(&environment, TargetOs::Windows));
+
+    // `try_all_caches` keeps the default application cache that was disabled
+    let locations = CacheDirConfig::new("app")
+                                   .app_cache(true)
+                                   .app_cache(false)
+                                   .try_all_caches()
+                                   .environment(&environment)
+                                   .locations(TargetOs::Windows).unwrap();
+    assert_eq!(CacheKind::App, locations[0].kind());
+}
+
+#[test]
+fn cache_names_are_validated_for_the_target_os() {
+    let location = |cache_name: &str, target_os: TargetOs| {
+        let mut environment = CustomEnvironment::new();
+        environment.set_home_dir("/home/me")
+                   .set_var("LOCALAPPDATA", r"C:\Users\me\AppData\Local");
+        CacheDirConfig::new(cache_name)
+                       .nested_cache_name(true)
+                       .environment(&environment)
+                       .locations(target_os)
+                       .map(|locations| locations[0].path().as_os_str().to_os_string())
+    };
+
+    // The Windows separators, drive prefixes and roots are checked on every host
+    assert_eq!(Err(CacheNameError::ParentDir), location(r"app\..\..\escaped", TargetOs::Windows));
+    assert_eq!(Err(CacheNameError::ParentDir), location("app/../../escaped", TargetOs::Windows));
+    assert_eq!(Err(CacheNameError::Absolute), location(r"C:\escaped", TargetOs::Windows));
+    assert_eq!(Err(CacheNameError::Absolute), location("C:escaped", TargetOs::Windows));
+    assert_eq!(Err(CacheNameError::Absolute), location(r"\escaped", TargetOs::Windows));
+    assert_eq!(Err(CacheNameError::Absolute), location(r"\\server\share", TargetOs::Windows));
+    assert_eq!(Err(CacheNameError::Reserved("nul.txt".to_string())),
+               location(r"app\nul.txt", TargetOs::Windows));
+    assert_eq!(Err(CacheNameError::Absolute), location("/escaped", TargetOs::Linux));
+    assert_eq!(Err(CacheNameError::ParentDir), location("app/../..", TargetOs::MacOs));
+
+    // The nested names are joined with the separator of the target OS
+    assert_eq!(Ok(OsStr::new(r"C:\Users\me\AppData\Local\org\app").to_os_string()),
+               location("org/app", TargetOs::Windows));
+    assert_eq!(Ok(OsStr::new(r"C:\Users\me\AppData\Local\org\app").to_os_string()),
+               location(r".\org\app", TargetOs::Windows));
+    assert_eq!(Ok(OsStr::new("/home/me/.cache/org/app").to_os_string()),
+               location("org/app", TargetOs::Linux));
+
+    // `\` and `:` are plain characters on Unix
+    if cfg!(unix) {
+        assert_eq!(Ok(OsStr::new(r"/home/me/.cache/C:\app").to_os_string()),
+                   location(r"C:\app", TargetOs::Linux));
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn shared_caches_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cache_name = unique_name("private_tmp_cache");
+    let mut cleanup = RemoveOnDrop::new();
+    cleanup.push(Path::new("/var/tmp").join(&cache_name));
+
+    let cache_dir = CacheDirConfig::new(&cache_name)
+                                   .tmp_cache(true)
+                                   .per_user(CacheKind::Tmp, false)
+                                   .get_cache_dir().unwrap();
+    assert_eq!(Path::new("/var/tmp").join(&cache_name), cache_dir.as_path());
+    assert_eq!(None, cache_dir.user_segment());
+    assert_eq!(0o700, fs::metadata(&cache_dir).unwrap().permissions().mode() & 0o777);
+}
+
+#[test]
+#[cfg(unix)]
+fn shared_caches_are_per_user_by_default() {
+    use std::os::unix::fs::{ MetadataExt, PermissionsExt };
+
+    let mut cleanup = RemoveOnDrop::new();
+    let cache_dir = CacheDirConfig::new(&unique_name("per_user_tmp_cache"))
+                                   .tmp_cache(true)
+                                   .get_cache_dir().unwrap();
+    cleanup.push(&cache_dir);
+    let user_dir = cache_dir.parent().unwrap();
+    let metadata = fs::metadata(user_dir).unwrap();
+
+    // The segment is the effective uid, which owns the per-user directory
+    assert_eq!(Some(OsStr::new(&metadata.uid().to_string())), cache_dir.user_segment());
+    assert_eq!(Path::new("/var/tmp").join(cache_dir.user_segment().unwrap()), user_dir);
+    assert_eq!(0o700, metadata.permissions().mode() & 0o777);
+    assert_eq!(0o700, fs::metadata(&cache_dir).unwrap().permissions().mode() & 0o777);
+}
+
+#[test]
+#[cfg(unix)]
+fn per_user_applies_only_to_shared_caches() {
+    let app_cache_path = TestDir::new("per_user_app");
+    let cache_dir = CacheDirConfig::new("__cachedir_test_per_user")
+                                   .app_cache_path(&app_cache_path)
+                                   .per_user(CacheKind::App, true)
+                                   .get_cache_dir().unwrap();
+    assert_eq!(app_cache_path.join("__cachedir_test_per_user"), cache_dir.as_path());
+    assert_eq!(None, cache_dir.user_segment());
+
+    let candidates = CacheDirConfig::new("__cachedir_test_per_user")
+                                    .sys_cache(true)
+                                    .per_user(CacheKind::System, true)
+                                    .candidates().unwrap();
+    assert_eq!(CacheKind::System, candidates[0].kind());
+    assert_eq!(Path::new("/var/cache"),
+               candidates[0].path().parent().unwrap().parent().unwrap());
+}
+
+#[test]
+#[cfg(unix)]
+fn shared_caches_reject_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let cache_name = unique_name("symlinked_tmp_cache");
+    let target = env::temp_dir().join(unique_name("symlink_target"));
+    let link = Path::new("/var/tmp").join(&cache_name);
+    let mut cleanup = RemoveOnDrop::new();
+    cleanup.push(&link);
+    cleanup.push(&target);
+    fs::create_dir_all(&target).unwrap();
+    symlink(&target, &link).unwrap();
+
+    let candidates = CacheDirConfig::new(&cache_name)
+                                    .tmp_cache(true)
+                                    .per_user(CacheKind::Tmp, false)
+                                    .candidates().unwrap();
+    assert_eq!(link, candidates[0].path());
+    assert_eq!(CandidateStatus::Insecure, candidates[0].status());
+
+    let result = CacheDirConfig::new(&cache_name)
+                                .tmp_cache(true)
+                                .per_user(CacheKind::Tmp, false)
+                                .get_cache_dir();
+    match result {
+        // The cache was created in the next tmp directory
+        Ok(cache_dir) => {
+            cleanup.push(&cache_dir);
+            assert!(cache_dir.as_path() != link);
+        },
+        Err(err)      => {
+            assert_eq!(Some(link.as_path()), err.attempts()[0].path());
+            assert_eq!(io::ErrorKind::PermissionDenied, err.attempts()[0].error().kind());
+        }
+    }
+}
+
+// Giving the directory to another user is only possible when the tests are running as `root`:
+// run it with `sudo -E cargo test -- --ignored`
+#[test]
+#[cfg(unix)]
+#[ignore = "requires root"]
+fn shared_caches_reject_directories_owned_by_other_users() {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+
+    let cache_name = unique_name("foreign_tmp_cache");
+    let foreign_dir = Path::new("/var/tmp").join(&cache_name);
+    let mut cleanup = RemoveOnDrop::new();
+    cleanup.push(&foreign_dir);
+    fs::create_dir_all(&foreign_dir).unwrap();
+
+    let path = CString::new(foreign_dir.as_os_str().as_bytes()).unwrap();
+    assert_eq!(0, unsafe { libc::chown(path.as_ptr(), 65534, 65534) },
+               "the tests must run as root");
+    assert_eq!(65534, fs::metadata(&foreign_dir).unwrap().uid());
+
+    let candidates = CacheDirConfig::new(&cache_name)
+                                    .tmp_cache(true)
+                                    .per_user(CacheKind::Tmp, false)
+                                    .candidates().unwrap();
+    assert_eq!(CandidateStatus::Insecure, candidates[0].status());
+
+    let result = CacheDirConfig::new(&cache_name)
+                                .tmp_cache(true)
+                                .per_user(CacheKind::Tmp, false)
+                                .get_cache_dir();
+    if let Ok(cache_dir) = result {
+        cleanup.push(&cache_dir);
+        assert!(cache_dir.as_path() != foreign_dir);
     }
 }